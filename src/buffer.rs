@@ -0,0 +1,326 @@
+use crate::document::Document;
+use crate::highlighting::TokenType;
+use crate::terminal;
+
+const BRACKET_PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+const DEFAULT_X_POSITION: usize = usize::MIN;
+const DEFAULT_Y_POSITION: usize = usize::MIN;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Position {
+    fn is_before(self, other: Position) -> bool {
+        (self.y, self.x) < (other.y, other.x)
+    }
+}
+
+/// An open document together with the cursor and scroll state it was last
+/// viewed with. The selection spans from `selection_anchor` to the cursor.
+/// The `x` of the cursor and the anchor is a byte index into the row, while
+/// the `x` of `screen_offset` is the first screen column shown.
+pub struct Buffer {
+    pub document: Document,
+    pub cursor_position: Position,
+    pub screen_offset: Position,
+    pub selection_anchor: Option<Position>,
+}
+
+impl Buffer {
+    pub fn new(document: Document) -> Self {
+        Buffer {
+            document,
+            cursor_position: Position::default(),
+            screen_offset: Position::default(),
+            selection_anchor: None,
+        }
+    }
+
+    /// Moves the cursor to the byte `x` of row `y`, clamped to the document
+    /// and moved back to the start of the character it falls into.
+    pub fn goto(&mut self, y: usize, x: usize) {
+        self.cursor_position.y = y.min(self.document.len().saturating_sub(1));
+        self.cursor_position.x = self.document.char_boundary(self.cursor_position.y, x);
+    }
+
+    /// Inserts `c` at the cursor, or with `overwrite` replaces the character
+    /// under it. A line break is always inserted, and past the end of the row
+    /// the row grows either way.
+    pub fn add_char(&mut self, c: char, overwrite: bool) {
+        let Position { x, y } = self.cursor_position;
+        if c == '\n' {
+            self.document.insert_char(y, x, c);
+            self.move_right();
+        } else if overwrite && x < self.document.get_row(y).len() {
+            self.cursor_position.x = self.document.replace_char(y, x, c);
+        } else {
+            self.document.insert_char(y, x, c);
+            self.cursor_position.x = x + c.len_utf8();
+        }
+    }
+
+    /// Inserts `text`, which may span several lines, at the cursor and moves
+    /// the cursor to its end.
+    pub fn insert_text(&mut self, text: &str) {
+        let (y, x) =
+            self.document
+                .insert_text(self.cursor_position.y, self.cursor_position.x, text);
+        self.cursor_position = Position { x, y };
+    }
+
+    pub fn remove_char(&mut self) {
+        if self.cursor_position.x > DEFAULT_X_POSITION {
+            self.move_left();
+            self.document
+                .remove_char(self.cursor_position.y, self.cursor_position.x);
+        } else if self.cursor_position.y > DEFAULT_Y_POSITION {
+            let current_row_num = self.cursor_position.y;
+            self.move_left();
+            self.document.join_row_with_previous(current_row_num);
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        self.move_to_row(self.cursor_position.y.saturating_sub(1));
+    }
+
+    pub fn move_down(&mut self) {
+        if self.cursor_position.y < self.document.len() - 1 {
+            self.move_to_row(self.cursor_position.y.saturating_add(1));
+        }
+    }
+
+    /// Moves the cursor to row `y`, keeping its column where the row is long
+    /// enough.
+    fn move_to_row(&mut self, y: usize) {
+        let Position { x, y: current_y } = self.cursor_position;
+        let column = self.document.char_column(current_y, x);
+        self.cursor_position = Position {
+            x: self.document.column_index(y, column),
+            y,
+        };
+    }
+
+    pub fn move_left(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        if x == DEFAULT_X_POSITION && y != DEFAULT_Y_POSITION {
+            self.cursor_position.y = y.saturating_sub(1);
+            self.cursor_position.x = self.document.get_row(self.cursor_position.y).len();
+        } else {
+            let previous = self.document.get_row(y)[..x].chars().next_back();
+            self.cursor_position.x = x - previous.map_or(0, char::len_utf8);
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        if let Some(c) = self.document.get_row(y)[x..].chars().next() {
+            self.cursor_position.x = x + c.len_utf8();
+        } else if self.cursor_position.y < self.document.len() - 1 {
+            self.cursor_position.y = self.cursor_position.y.saturating_add(1);
+            self.cursor_position.x = DEFAULT_X_POSITION;
+        }
+    }
+
+    pub fn jump_to_matching_bracket(&mut self) {
+        if let Some(position) = self.matching_bracket_position() {
+            self.cursor_position = position;
+        }
+    }
+
+    /// Finds the bracket paired with the one under the cursor. Brackets inside
+    /// strings and comments are skipped when the document has a known syntax.
+    pub fn matching_bracket_position(&self) -> Option<Position> {
+        let Position { x, y } = self.cursor_position;
+        let bracket = self.document.get_row(y)[x..].chars().next()?;
+        if self.document.token_type(y, self.document.char_column(y, x)) != TokenType::Normal {
+            return None;
+        }
+
+        BRACKET_PAIRS.iter().find_map(|&(open, close)| {
+            if bracket == open {
+                self.find_closing_bracket(open, close)
+            } else if bracket == close {
+                self.find_opening_bracket(open, close)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn find_closing_bracket(&self, open: char, close: char) -> Option<Position> {
+        let mut depth = 0usize;
+        let cursor = self.cursor_position;
+        for y in cursor.y..self.document.len() {
+            // Token types are looked up by character, positions are bytes.
+            for (column, (x, c)) in self.document.get_row(y).char_indices().enumerate() {
+                if (y == cursor.y && x <= cursor.x)
+                    || self.document.token_type(y, column) != TokenType::Normal
+                {
+                    continue;
+                }
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        return Some(Position { x, y });
+                    }
+                    depth -= 1;
+                }
+            }
+        }
+        None
+    }
+
+    fn find_opening_bracket(&self, open: char, close: char) -> Option<Position> {
+        let mut depth = 0usize;
+        let cursor = self.cursor_position;
+        for y in (0..=cursor.y).rev() {
+            let row = self.document.get_row(y);
+            let end = if y == cursor.y { cursor.x } else { row.len() };
+            let chars: Vec<(usize, char)> = row[..end].char_indices().collect();
+
+            for (column, &(x, c)) in chars.iter().enumerate().rev() {
+                if self.document.token_type(y, column) != TokenType::Normal {
+                    continue;
+                }
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    if depth == 0 {
+                        return Some(Position { x, y });
+                    }
+                    depth -= 1;
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the ordered start and (exclusive) end of the selection.
+    pub fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.cursor_position;
+        if anchor == cursor {
+            None
+        } else if anchor.is_before(cursor) {
+            Some((anchor, cursor))
+        } else {
+            Some((cursor, anchor))
+        }
+    }
+
+    /// Selects the word at `position`, or the single character there when it
+    /// is not part of a word.
+    pub fn select_word(&mut self, position: Position) {
+        self.goto(position.y, position.x);
+        let Position { x, y } = self.cursor_position;
+        let row = self.document.get_row(y);
+        let Some(c) = row[x..].chars().next() else {
+            self.selection_anchor = None;
+            return;
+        };
+
+        let (start, end) = if is_word_char(c) {
+            let start = row[..x]
+                .char_indices()
+                .rev()
+                .take_while(|(_, c)| is_word_char(*c))
+                .last()
+                .map_or(x, |(index, _)| index);
+            let end = row[x..]
+                .char_indices()
+                .find(|(_, c)| !is_word_char(*c))
+                .map_or(row.len(), |(index, _)| x + index);
+            (start, end)
+        } else {
+            (x, x + c.len_utf8())
+        };
+        self.selection_anchor = Some(Position { x: start, y });
+        self.cursor_position.x = end;
+    }
+
+    /// Scrolls the view by `lines` (negative is up) and moves the cursor the
+    /// least needed to stay inside the `height` rows tall view.
+    pub fn scroll(&mut self, lines: isize, height: usize) {
+        scroll_view(
+            &self.document,
+            &mut self.cursor_position,
+            &mut self.screen_offset,
+            lines,
+            height,
+        );
+    }
+
+    pub fn center_offsets(&mut self, width: usize, height: usize) {
+        self.screen_offset.y = self.cursor_position.y.saturating_sub(height / 2);
+        self.change_offsets(width, height);
+    }
+
+    pub fn change_offsets(&mut self, width: usize, height: usize) {
+        scroll_to_cursor(
+            &self.document,
+            self.cursor_position,
+            &mut self.screen_offset,
+            width,
+            height,
+        );
+    }
+}
+
+/// Moves `screen_offset` just enough for `cursor_position` to be visible in a
+/// `width` x `height` viewport.
+pub fn scroll_to_cursor(
+    document: &Document,
+    cursor_position: Position,
+    screen_offset: &mut Position,
+    width: usize,
+    height: usize,
+) {
+    if cursor_position.y < screen_offset.y {
+        screen_offset.y = cursor_position.y;
+    } else if cursor_position.y >= screen_offset.y.saturating_add(height) {
+        screen_offset.y = cursor_position.y.saturating_sub(height).saturating_add(1);
+    }
+
+    // Both halves of a double-width character under the cursor are shown.
+    let y = cursor_position.y;
+    let x = document.char_boundary(y, cursor_position.x);
+    let column = document.display_column(y, x);
+    let end = column
+        + document.get_row(y)[x..]
+            .chars()
+            .next()
+            .map_or(1, |c| terminal::symbol_width(c).max(1));
+    if column < screen_offset.x {
+        screen_offset.x = column;
+    } else if end > screen_offset.x.saturating_add(width) {
+        screen_offset.x = end.saturating_sub(width);
+    }
+}
+
+/// `Buffer::scroll` for views whose cursor and offsets are not kept in the
+/// buffer.
+pub fn scroll_view(
+    document: &Document,
+    cursor_position: &mut Position,
+    screen_offset: &mut Position,
+    lines: isize,
+    height: usize,
+) {
+    let last_row = document.len().saturating_sub(1);
+    screen_offset.y = screen_offset.y.saturating_add_signed(lines).min(last_row);
+    let last_visible_row = screen_offset
+        .y
+        .saturating_add(height.saturating_sub(1))
+        .min(last_row);
+    cursor_position.y = cursor_position.y.clamp(screen_offset.y, last_visible_row);
+    cursor_position.x = document.char_boundary(cursor_position.y, cursor_position.x);
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PATH_SEPARATOR: char = '/';
+const HOME_PREFIX: &str = "~/";
+
+/// Lists the completions of the last component of the path in `input`, each
+/// as the whole completed input. Relative paths are looked up in `base_dir`
+/// and a leading `~` stands for the home directory, while the input keeps
+/// them as typed. Directories end with a slash, so the next component can be
+/// typed right away, and hidden entries are only offered once the component
+/// starts with a dot.
+pub fn candidates(input: &str, base_dir: &Path) -> Vec<String> {
+    if input == "~" {
+        return vec![String::from(HOME_PREFIX)];
+    }
+    let (dir, prefix) = match input.rfind(PATH_SEPARATOR) {
+        Some(index) => input.split_at(index + 1),
+        None => ("", input),
+    };
+    let Ok(entries) = fs::read_dir(resolve(dir, base_dir)) else {
+        return vec![];
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let separator = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{dir}{name}{separator}"))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// The longest prefix all `candidates` start with.
+pub fn common_prefix(candidates: &[String]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let len = rest.iter().fold(first.len(), |len, candidate| {
+        first[..len]
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((index, c), _)| index + c.len_utf8())
+    });
+    first[..len].to_owned()
+}
+
+/// Turns a path typed into a prompt into the path to use: a leading `~` is
+/// replaced by the home directory, and a relative path is taken relative to
+/// `base_dir`.
+pub fn resolve(path: &str, base_dir: &Path) -> PathBuf {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let expanded = match (path.strip_prefix(HOME_PREFIX), home) {
+        (Some(rest), Some(home)) => home.join(rest),
+        (None, Some(home)) if path == "~" => home,
+        _ => PathBuf::from(path),
+    };
+    if expanded.is_relative() && base_dir != Path::new(".") {
+        base_dir.join(expanded)
+    } else if expanded.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        expanded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes_paths_in_base_dir() {
+        let root = env::temp_dir().join(format!("rte-complete-{}", std::process::id()));
+        for dir in ["docs", "drafts", ".git"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("data.txt"), "").unwrap();
+        fs::write(root.join("docs").join("guide.md"), "").unwrap();
+
+        assert_eq!(candidates("d", &root), ["data.txt", "docs/", "drafts/"]);
+        assert_eq!(candidates("docs/", &root), ["docs/guide.md"]);
+        assert_eq!(candidates(".g", &root), [".git/"]);
+        assert!(candidates("missing/", &root).is_empty());
+        assert_eq!(candidates("~", &root), ["~/"]);
+        assert_eq!(common_prefix(&candidates("d", &root)), "d");
+        assert_eq!(
+            common_prefix(&[String::from("drafts/"), String::from("drafted")]),
+            "draft"
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn resolves_home_and_relative_paths() {
+        let home = PathBuf::from(env::var_os("HOME").unwrap());
+        let base_dir = Path::new("src");
+        assert_eq!(resolve("~/notes.txt", base_dir), home.join("notes.txt"));
+        assert_eq!(resolve("main.rs", base_dir), Path::new("src/main.rs"));
+        assert_eq!(resolve("/etc/hosts", base_dir), Path::new("/etc/hosts"));
+        assert_eq!(resolve("main.rs", Path::new(".")), Path::new("main.rs"));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::error::{self, Error};
+use crate::status_line::{self, StatusLine};
+use crate::terminal::CursorShape;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const CONFIG_DIR_NAME: &str = env!("CARGO_PKG_NAME");
+const CONFIG_FILE_NAME: &str = "config";
+const STATUS_LEFT_KEY: &str = "status_left";
+const STATUS_RIGHT_KEY: &str = "status_right";
+const WINDOW_TITLE_KEY: &str = "window_title";
+const CURSOR_SHAPE_KEY: &str = "cursor_shape";
+const PROMPT_CURSOR_SHAPE_KEY: &str = "prompt_cursor_shape";
+const OVERWRITE_CURSOR_SHAPE_KEY: &str = "overwrite_cursor_shape";
+const CURSOR_LINE_KEY: &str = "cursor_line";
+const RULER_KEY: &str = "ruler";
+
+/// The settings read from the configuration file. Every setting has a
+/// default, so the file and any of its lines may be left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub status_line: StatusLine,
+    /// Whether the window title shows the active document.
+    pub window_title: bool,
+    /// The cursor shape while editing text.
+    pub cursor_shape: CursorShape,
+    /// The cursor shape while typing into a prompt.
+    pub prompt_cursor_shape: CursorShape,
+    /// The cursor shape while typing replaces text.
+    pub overwrite_cursor_shape: CursorShape,
+    /// Whether the line of the cursor is highlighted.
+    pub cursor_line: bool,
+    /// Highlights the column right after this many characters.
+    pub ruler: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            status_line: StatusLine::default(),
+            window_title: true,
+            cursor_shape: CursorShape::Default,
+            prompt_cursor_shape: CursorShape::Default,
+            overwrite_cursor_shape: CursorShape::Underline { blinking: false },
+            cursor_line: false,
+            ruler: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads `config` from the configuration directory, falling back to the
+    /// defaults when there is no such file.
+    pub fn load() -> error::Result<Self> {
+        let Some(path) = config_dir()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .filter(|path| path.is_file())
+        else {
+            return Ok(Config::default());
+        };
+        let origin = path.display().to_string();
+        let source = fs::read_to_string(&path).map_err(|err| Error::document(&origin, err))?;
+        Self::parse(&source, &origin)
+    }
+
+    fn parse(source: &str, origin: &str) -> error::Result<Self> {
+        let mut config = Config::default();
+        parse_entries(source, origin, |key, value| {
+            match key {
+                STATUS_LEFT_KEY => config.status_line.left = status_line::parse_segments(value)?,
+                STATUS_RIGHT_KEY => config.status_line.right = status_line::parse_segments(value)?,
+                WINDOW_TITLE_KEY => config.window_title = parse_bool(value)?,
+                CURSOR_SHAPE_KEY => config.cursor_shape = parse_cursor_shape(value)?,
+                PROMPT_CURSOR_SHAPE_KEY => config.prompt_cursor_shape = parse_cursor_shape(value)?,
+                OVERWRITE_CURSOR_SHAPE_KEY => {
+                    config.overwrite_cursor_shape = parse_cursor_shape(value)?;
+                },
+                CURSOR_LINE_KEY => config.cursor_line = parse_bool(value)?,
+                RULER_KEY => {
+                    let ruler = value
+                        .parse()
+                        .map_err(|_| format!("expected a column number, got `{value}`"))?;
+                    // A ruler at 0 turns it off.
+                    config.ruler = Some(ruler).filter(|ruler| *ruler > 0);
+                },
+                _ => return Err(format!("unknown setting `{key}`")),
+            }
+            Ok(())
+        })?;
+        Ok(config)
+    }
+}
+
+/// The directory holding the user configuration: `$XDG_CONFIG_HOME/rte`,
+/// or `~/.config/rte` when that is not set.
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join(CONFIG_DIR_NAME))
+}
+
+/// Calls `apply` with the trimmed key and value of every `key = value` line
+/// of a configuration or theme file, skipping blank lines and `#` comments.
+/// `origin` names the file in error messages.
+pub fn parse_entries(
+    source: &str,
+    origin: &str,
+    mut apply: impl FnMut(&str, &str) -> Result<(), String>,
+) -> error::Result<()> {
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: String| Error::Config(format!("{origin}:{}: {message}", index + 1));
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(invalid(String::from("expected `key = value`")));
+        };
+        apply(key.trim(), value.trim()).map_err(invalid)?;
+    }
+    Ok(())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("expected `true` or `false`, got `{value}`")),
+    }
+}
+
+/// Parses `default`, or `block`, `underline` or `bar` optionally followed
+/// by `blinking`.
+fn parse_cursor_shape(value: &str) -> Result<CursorShape, String> {
+    let invalid = || format!("unknown cursor shape `{value}`");
+    let mut words = value.split_whitespace();
+    let shape = words.next().ok_or_else(invalid)?;
+    let blinking = match words.next() {
+        None => false,
+        Some("blinking") => true,
+        Some(_) => return Err(invalid()),
+    };
+    if words.next().is_some() {
+        return Err(invalid());
+    }
+    match shape {
+        "default" if !blinking => Ok(CursorShape::Default),
+        "block" => Ok(CursorShape::Block { blinking }),
+        "underline" => Ok(CursorShape::Underline { blinking }),
+        "bar" => Ok(CursorShape::Bar { blinking }),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status_line::Segment;
+
+    #[test]
+    fn parses_settings() {
+        let config = Config::parse(
+            "# status line\nstatus_left = name flags\n\nstatus_right =\nwindow_title = false\n\
+             cursor_shape = bar blinking\nprompt_cursor_shape = underline\n\
+             overwrite_cursor_shape = block\nruler = 80\n",
+            "config",
+        )
+        .unwrap();
+        assert_eq!(
+            config.status_line,
+            StatusLine {
+                left: vec![Segment::FileName, Segment::Flags],
+                right: vec![],
+            }
+        );
+        assert!(!config.window_title);
+        assert_eq!(config.cursor_shape, CursorShape::Bar { blinking: true });
+        assert_eq!(
+            config.prompt_cursor_shape,
+            CursorShape::Underline { blinking: false }
+        );
+        assert_eq!(
+            config.overwrite_cursor_shape,
+            CursorShape::Block { blinking: false }
+        );
+        assert_eq!(config.ruler, Some(80));
+
+        let error = |source| Config::parse(source, "config").unwrap_err().to_string();
+        assert_eq!(
+            error("status_left = name\nstatus = name"),
+            "invalid configuration: config:2: unknown setting `status`"
+        );
+        assert_eq!(
+            error("window_title = yes"),
+            "invalid configuration: config:1: expected `true` or `false`, got `yes`"
+        );
+        assert_eq!(
+            error("cursor_shape = default blinking"),
+            "invalid configuration: config:1: unknown cursor shape `default blinking`"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+/// Unchanged lines shown around every change.
+const CONTEXT_LINES: usize = 3;
+/// Above this many cells the table of common subsequences gets too large, and
+/// the changed lines are reported as removed and added as a whole.
+const MAX_TABLE_SIZE: usize = 1 << 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Remove,
+    Add,
+}
+
+/// Compares `old` with `new` line by line and returns the differences in the
+/// unified diff format, with the sides labelled `old_name` and `new_name`.
+/// Returns no lines when both are equal.
+pub fn unified(old: &[String], new: &[String], old_name: &str, new_name: &str) -> Vec<String> {
+    let edits = edit_script(old, new);
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|index| edits[*index] != Edit::Keep)
+        .collect();
+    if changes.is_empty() {
+        return vec![];
+    }
+
+    // The old and new line numbers each edit starts at.
+    let mut starts = Vec::with_capacity(edits.len());
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in &edits {
+        starts.push((old_line, new_line));
+        match edit {
+            Edit::Keep => {
+                old_line += 1;
+                new_line += 1;
+            },
+            Edit::Remove => old_line += 1,
+            Edit::Add => new_line += 1,
+        }
+    }
+
+    let mut lines = vec![format!("--- {old_name}"), format!("+++ {new_name}")];
+    let mut change = 0;
+    while change < changes.len() {
+        let start = changes[change].saturating_sub(CONTEXT_LINES);
+        let mut end = changes[change] + 1;
+        // Changes whose context would touch share a hunk.
+        while change + 1 < changes.len() && changes[change + 1] - end <= CONTEXT_LINES * 2 {
+            change += 1;
+            end = changes[change] + 1;
+        }
+        change += 1;
+        let end = (end + CONTEXT_LINES).min(edits.len());
+
+        let hunk = &edits[start..end];
+        let old_count = hunk.iter().filter(|edit| **edit != Edit::Add).count();
+        let new_count = hunk.iter().filter(|edit| **edit != Edit::Remove).count();
+        let (old_start, new_start) = starts[start];
+        lines.push(format!(
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        ));
+        for (edit, (old_line, new_line)) in hunk.iter().zip(&starts[start..end]) {
+            lines.push(match edit {
+                Edit::Keep => format!(" {}", old[*old_line]),
+                Edit::Remove => format!("-{}", old[*old_line]),
+                Edit::Add => format!("+{}", new[*new_line]),
+            });
+        }
+    }
+    lines
+}
+
+/// Turns `old` into `new` keeping a longest common subsequence of lines,
+/// after skipping their common beginning and end.
+fn edit_script(old: &[String], new: &[String]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut edits = vec![Edit::Keep; prefix];
+    let (rows, columns) = (old_middle.len() + 1, new_middle.len() + 1);
+    if rows.saturating_mul(columns) > MAX_TABLE_SIZE {
+        edits.extend(std::iter::repeat_n(Edit::Remove, old_middle.len()));
+        edits.extend(std::iter::repeat_n(Edit::Add, new_middle.len()));
+    } else {
+        // `common[i * columns + j]` is the length of the longest common
+        // subsequence of `old_middle[i..]` and `new_middle[j..]`.
+        let mut common = vec![0_u32; rows * columns];
+        for i in (0..old_middle.len()).rev() {
+            for j in (0..new_middle.len()).rev() {
+                common[i * columns + j] = if old_middle[i] == new_middle[j] {
+                    common[(i + 1) * columns + j + 1] + 1
+                } else {
+                    common[(i + 1) * columns + j].max(common[i * columns + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < old_middle.len() || j < new_middle.len() {
+            if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+                edits.push(Edit::Keep);
+                i += 1;
+                j += 1;
+            } else if j == new_middle.len()
+                || (i < old_middle.len()
+                    && common[(i + 1) * columns + j] >= common[i * columns + j + 1])
+            {
+                edits.push(Edit::Remove);
+                i += 1;
+            } else {
+                edits.push(Edit::Add);
+                j += 1;
+            }
+        }
+    }
+    edits.extend(std::iter::repeat_n(Edit::Keep, suffix));
+    edits
+}
+
+/// Formats the 0-based `start` and the `count` of lines of a hunk side. An
+/// empty side names the line before it.
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{count}", start + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(String::from).collect()
+    }
+
+    #[test]
+    fn produces_unified_hunks() {
+        let old = lines("a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm");
+        let new = lines("a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nm\nn");
+
+        assert_eq!(
+            unified(&old, &new, "old", "new"),
+            [
+                "--- old",
+                "+++ new",
+                "@@ -1,5 +1,5 @@",
+                " a",
+                "-b",
+                "+B",
+                " c",
+                " d",
+                " e",
+                "@@ -9,5 +9,5 @@",
+                " i",
+                " j",
+                " k",
+                "-l",
+                " m",
+                "+n",
+            ]
+        );
+        assert!(unified(&old, &old, "old", "new").is_empty());
+        assert_eq!(
+            unified(&lines("a"), &lines("a\nb"), "old", "new"),
+            ["--- old", "+++ new", "@@ -1 +1,2 @@", " a", "+b"]
+        );
+    }
+}
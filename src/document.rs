@@ -1,24 +1,125 @@
-use std::fs::File;
+use crate::diff;
+use crate::error::{self, Error};
+use crate::highlighting::{HighlightState, Syntax, TokenType};
+use crate::terminal;
+use std::ffi::CString;
+use std::fs::{self, File};
 use std::io::{self, prelude::*, BufReader, LineWriter};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
 use std::path::Path;
+use std::time::SystemTime;
 
 const NEW_LINE_CHARACTER: char = '\n';
+const CARRIAGE_RETURN_CHARACTER: char = '\r';
+const READ_ONLY_ERROR: &str = "the buffer is read-only";
+const SCRATCH_ERROR: &str = "the buffer has no file";
+
+/// How rows are separated in the file. Taken from the first line break when
+/// reading, and used for every row when writing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// What identifies a version of a file on disk. Build tools and version
+/// control replace files rather than write into them, so the inode is checked
+/// besides the modification time and size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+    inode: u64,
+}
+
+impl DiskState {
+    /// Returns `None` when the file does not exist or can't be inspected.
+    fn of(file_path: &str) -> Option<Self> {
+        let metadata = fs::metadata(file_path).ok()?;
+        Some(DiskState {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            inode: metadata.ino(),
+        })
+    }
+}
 
 pub struct Document {
     rows: Vec<String>,
     pub file_path: String,
+    line_ending: LineEnding,
     is_modified: bool,
+    /// Refuses every change to the content.
+    read_only: bool,
+    /// Generated content without a file, which `file_path` only names.
+    scratch: bool,
+    /// The file as last read or written.
+    disk_state: Option<DiskState>,
+    /// The last change on disk reported by `poll_disk_change`.
+    reported_disk_state: Option<DiskState>,
+    syntax: Option<&'static Syntax>,
+    highlights: Vec<RowHighlight>,
+}
+
+struct RowHighlight {
+    tokens: Vec<TokenType>,
+    end_state: HighlightState,
 }
 
 impl Document {
-    pub fn new(file_path: &str) -> Result<Self, io::Error> {
+    pub fn new(file_path: &str) -> error::Result<Self> {
+        // Taken before reading, so a write in between shows up as a change.
+        let disk_state = DiskState::of(file_path);
+        let (rows, line_ending) =
+            Self::read_rows(file_path).map_err(|err| Error::document(file_path, err))?;
+        let mut document = Self::from_rows(file_path, rows);
+        document.line_ending = line_ending;
+        document.disk_state = disk_state;
+        document.read_only = !document.is_writable();
+        Ok(document)
+    }
+
+    fn read_rows(file_path: &str) -> Result<(Vec<String>, LineEnding), io::Error> {
         let mut document_rows = vec![];
+        let mut line_ending = None;
         match File::open(Path::new(file_path)) {
             Ok(file) => {
                 if file.metadata()?.len() > 0 {
-                    let reader = BufReader::new(file);
-                    for line in reader.lines() {
-                        document_rows.push(line?);
+                    let mut reader = BufReader::new(file);
+                    let mut line = String::new();
+                    while reader.read_line(&mut line)? > 0 {
+                        if line.ends_with(NEW_LINE_CHARACTER) {
+                            line.pop();
+                            let crlf = line.ends_with(CARRIAGE_RETURN_CHARACTER);
+                            if crlf {
+                                line.pop();
+                            }
+                            line_ending.get_or_insert(if crlf {
+                                LineEnding::CrLf
+                            } else {
+                                LineEnding::Lf
+                            });
+                        }
+                        document_rows.push(mem::take(&mut line));
                     }
                 } else {
                     document_rows.push(String::new());
@@ -30,41 +131,270 @@ impl Document {
                 },
                 _ => return Err(e),
             },
-        };
+        }
 
-        Ok(Self {
+        Ok((document_rows, line_ending.unwrap_or_default()))
+    }
+
+    #[cfg(test)]
+    pub fn from_text(file_path: &str, text: &str) -> Self {
+        Self::from_rows(
+            file_path,
+            text.split(NEW_LINE_CHARACTER).map(String::from).collect(),
+        )
+    }
+
+    /// Creates a document that is not read from `file_path`, such as a
+    /// generated diff. `rows` must not be empty.
+    pub fn from_rows(file_path: &str, rows: Vec<String>) -> Self {
+        let mut document = Self {
             is_modified: false,
-            rows: document_rows,
+            read_only: false,
+            scratch: false,
+            rows,
             file_path: String::from(file_path),
-        })
+            line_ending: LineEnding::default(),
+            disk_state: None,
+            reported_disk_state: None,
+            syntax: Syntax::for_file(file_path),
+            highlights: vec![],
+        };
+        document.update_highlights(0, document.len());
+        document
+    }
+
+    /// Creates a read-only document of generated `rows`, such as the help,
+    /// labelled `name`. It has no file to save to or to watch for changes.
+    pub fn scratch(name: &str, rows: Vec<String>) -> Self {
+        let mut document = Self::from_rows(name, rows);
+        document.read_only = true;
+        document.scratch = true;
+        document
+    }
+
+    /// Writes the content to its file. A read-only document is only written
+    /// when `force` is set, a scratch document never.
+    pub fn save(&mut self, force: bool) -> error::Result<()> {
+        self.check_file()?;
+        if self.read_only && !force {
+            let err = io::Error::new(io::ErrorKind::PermissionDenied, READ_ONLY_ERROR);
+            return Err(Error::document(&self.file_path, err));
+        }
+        self.write_to(&self.file_path)?;
+        self.is_modified = false;
+        self.disk_state = DiskState::of(&self.file_path);
+        Ok(())
     }
 
-    pub fn save(&mut self) -> Result<(), io::Error> {
-        let file = File::create(&self.file_path)?;
+    /// Writes the content to `file_path` and makes it the path of the
+    /// document, highlighted for its new file type. Allowed for a read-only
+    /// document, which is only kept read-only if the new file is not writable.
+    pub fn save_as(&mut self, file_path: &str) -> error::Result<()> {
+        self.write_to(file_path)?;
+        self.file_path = String::from(file_path);
+        self.scratch = false;
+        self.read_only = !self.is_writable();
+        self.is_modified = false;
+        self.disk_state = DiskState::of(file_path);
+        self.reported_disk_state = None;
+        self.syntax = Syntax::for_file(file_path);
+        self.highlights.clear();
+        self.update_highlights(0, self.len());
+        Ok(())
+    }
+
+    /// Replaces the content with the file on disk, dropping any changes.
+    pub fn reload(&mut self) -> error::Result<()> {
+        self.check_file()?;
+        let disk_state = DiskState::of(&self.file_path);
+        let (rows, line_ending) = Self::read_rows(&self.file_path)
+            .map_err(|err| Error::document(&self.file_path, err))?;
+        self.rows = rows;
+        self.line_ending = line_ending;
+        self.is_modified = false;
+        self.disk_state = disk_state;
+        self.highlights.clear();
+        self.update_highlights(0, self.len());
+        Ok(())
+    }
+
+    /// Whether the document was read from or written to a file that exists.
+    pub fn is_on_disk(&self) -> bool {
+        self.disk_state.is_some()
+    }
+
+    /// Whether the file was written by someone else since it was read or
+    /// saved. A deleted file does not count, as saving just recreates it.
+    pub fn changed_on_disk(&self) -> bool {
+        if self.scratch {
+            return false;
+        }
+        let current = DiskState::of(&self.file_path);
+        current.is_some() && current != self.disk_state
+    }
+
+    /// Like `changed_on_disk`, but reports every new version of the file only
+    /// once, so a periodic check does not ask again about a dismissed change.
+    pub fn poll_disk_change(&mut self) -> bool {
+        if self.scratch {
+            return false;
+        }
+        let current = DiskState::of(&self.file_path);
+        if current.is_none() || current == self.disk_state || current == self.reported_disk_state {
+            return false;
+        }
+        self.reported_disk_state = current;
+        true
+    }
+
+    /// Returns the differences from the file on disk to the content in the
+    /// unified diff format.
+    pub fn diff_with_disk(&self) -> error::Result<Vec<String>> {
+        self.check_file()?;
+        let (disk_rows, _) = Self::read_rows(&self.file_path)
+            .map_err(|err| Error::document(&self.file_path, err))?;
+        Ok(diff::unified(
+            &disk_rows,
+            &self.rows,
+            &format!("{} (on disk)", self.file_path),
+            &format!("{} (buffer)", self.file_path),
+        ))
+    }
+
+    fn check_file(&self) -> error::Result<()> {
+        if self.scratch {
+            let err = io::Error::new(io::ErrorKind::Unsupported, SCRATCH_ERROR);
+            return Err(Error::document(&self.file_path, err));
+        }
+        Ok(())
+    }
+
+    /// Writes the content to `file_path` without touching the modified flag,
+    /// even when the document is read-only.
+    pub fn write_to(&self, file_path: &str) -> error::Result<()> {
+        self.write_rows(file_path)
+            .map_err(|err| Error::document(file_path, err))
+    }
+
+    fn write_rows(&self, file_path: &str) -> Result<(), io::Error> {
+        let file = File::create(file_path)?;
         let mut writer = LineWriter::new(file);
 
         for (row_num, row_content) in self.rows.iter().enumerate() {
             let mut buf: Vec<u8> = row_content.bytes().collect();
             if row_num != self.rows.len() - 1 {
-                buf.push(NEW_LINE_CHARACTER as u8);
+                buf.extend_from_slice(self.line_ending.as_str().as_bytes());
             }
 
             writer.write_all(&buf[..])?;
         }
 
-        writer.flush()?;
-        self.is_modified = false;
-        Ok(())
+        writer.flush()
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// The last component of the file path.
+    pub fn file_name(&self) -> String {
+        Path::new(&self.file_path).file_name().map_or_else(
+            || self.file_path.clone(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    }
+
+    /// The file extension, which is what the highlighting is chosen by.
+    pub fn file_type(&self) -> Option<&str> {
+        Path::new(&self.file_path).extension()?.to_str()
     }
 
     pub fn is_modified(&self) -> bool {
         self.is_modified
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn is_scratch(&self) -> bool {
+        self.scratch
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Whether the file may be written, or created in its directory when it
+    /// does not exist yet. Files that are not writable are opened read-only.
+    pub fn is_writable(&self) -> bool {
+        let path = Path::new(&self.file_path);
+        match check_write_access(path) {
+            Ok(()) => true,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let dir = path
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                check_write_access(dir).is_ok()
+            },
+            Err(_) => false,
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.rows.len()
     }
 
+    /// Moves the byte `index` back to the start of the character it falls
+    /// into, clamped to the end of the row. Positions in a row are byte
+    /// indexes, which always have to be on such a boundary.
+    pub fn char_boundary(&self, row_num: usize, index: usize) -> usize {
+        let row = &self.rows[row_num];
+        (0..=index.min(row.len()))
+            .rev()
+            .find(|index| row.is_char_boundary(*index))
+            .unwrap_or_default()
+    }
+
+    /// The 0-based character column of the byte `index` in the row.
+    pub fn char_column(&self, row_num: usize, index: usize) -> usize {
+        let index = self.char_boundary(row_num, index);
+        self.rows[row_num][..index].chars().count()
+    }
+
+    /// The 0-based screen column of the byte `index` in the row.
+    pub fn display_column(&self, row_num: usize, index: usize) -> usize {
+        let index = self.char_boundary(row_num, index);
+        self.rows[row_num][..index]
+            .chars()
+            .map(terminal::symbol_width)
+            .sum()
+    }
+
+    /// The byte index of the character shown at the 0-based screen `column`,
+    /// or the end of the row when it is shorter.
+    pub fn display_index(&self, row_num: usize, column: usize) -> usize {
+        let row = &self.rows[row_num];
+        let mut end = 0;
+        for (index, symbol) in row.char_indices() {
+            end += terminal::symbol_width(symbol);
+            if end > column {
+                return index;
+            }
+        }
+        row.len()
+    }
+
+    /// The byte index of the character at the 0-based character `column`,
+    /// or the end of the row when it is shorter.
+    pub fn column_index(&self, row_num: usize, column: usize) -> usize {
+        let row = &self.rows[row_num];
+        row.char_indices()
+            .nth(column)
+            .map_or(row.len(), |(index, _)| index)
+    }
+
     pub fn get_row(&self, row_num: usize) -> &String {
         &self.rows[row_num]
     }
@@ -73,25 +403,224 @@ impl Document {
         self.rows.get(row_num)
     }
 
+    /// The token type of the character at the 0-based character `index`.
+    pub fn token_type(&self, row_num: usize, index: usize) -> TokenType {
+        self.highlights
+            .get(row_num)
+            .and_then(|highlight| highlight.tokens.get(index))
+            .copied()
+            .unwrap_or(TokenType::Normal)
+    }
+
     pub fn insert_char(&mut self, row_num: usize, index: usize, c: char) {
+        if self.read_only {
+            return;
+        }
         if c == NEW_LINE_CHARACTER {
             let new_row = self.rows[row_num].split_off(index);
             self.rows.insert(row_num.saturating_add(1), new_row);
+            if self.syntax.is_some() {
+                self.highlights.insert(
+                    row_num.saturating_add(1),
+                    RowHighlight {
+                        tokens: vec![],
+                        end_state: HighlightState::Normal,
+                    },
+                );
+            }
+            self.update_highlights(row_num, row_num.saturating_add(1));
         } else {
             self.rows[row_num].insert(index, c);
+            self.update_highlights(row_num, row_num);
         }
         self.is_modified = true;
     }
 
+    /// Replaces the character at the byte `index` with `c`, which must not be
+    /// a line break, and returns the index right after `c`. Both may have a
+    /// different length in UTF-8.
+    pub fn replace_char(&mut self, row_num: usize, index: usize, c: char) -> usize {
+        if self.read_only {
+            return index;
+        }
+        let row = &mut self.rows[row_num];
+        let Some((start, old)) = row
+            .char_indices()
+            .take_while(|(start, _)| *start <= index)
+            .last()
+        else {
+            return index;
+        };
+        row.replace_range(start..start + old.len_utf8(), c.encode_utf8(&mut [0; 4]));
+        self.update_highlights(row_num, row_num);
+        self.is_modified = true;
+        start + c.len_utf8()
+    }
+
+    /// Inserts `text`, which may span several lines with either line ending,
+    /// and returns the row and index right after it.
+    pub fn insert_text(&mut self, row_num: usize, index: usize, text: &str) -> (usize, usize) {
+        if self.read_only {
+            return (row_num, index);
+        }
+        let tail = self.rows[row_num].split_off(index);
+        let mut lines = text
+            .split(NEW_LINE_CHARACTER)
+            .map(|line| line.strip_suffix(CARRIAGE_RETURN_CHARACTER).unwrap_or(line));
+        self.rows[row_num].push_str(lines.next().unwrap_or_default());
+        let new_rows: Vec<String> = lines.map(String::from).collect();
+        let next_row = row_num + 1;
+        let last_row = row_num + new_rows.len();
+        if self.syntax.is_some() {
+            let placeholders = new_rows.iter().map(|_| RowHighlight {
+                tokens: vec![],
+                end_state: HighlightState::Normal,
+            });
+            self.highlights.splice(next_row..next_row, placeholders);
+        }
+        self.rows.splice(next_row..next_row, new_rows);
+
+        let end_index = self.rows[last_row].len();
+        self.rows[last_row].push_str(&tail);
+        self.update_highlights(row_num, last_row);
+        self.is_modified = true;
+        (last_row, end_index)
+    }
+
+    /// Removes the character starting at the byte `index`.
     pub fn remove_char(&mut self, row_num: usize, index: usize) {
+        if self.read_only {
+            return;
+        }
         self.rows[row_num].remove(index);
+        self.update_highlights(row_num, row_num);
         self.is_modified = true;
     }
 
     pub fn join_row_with_previous(&mut self, row_num: usize) {
+        if self.read_only {
+            return;
+        }
         let row = self.rows[row_num].clone();
         self.rows[row_num.saturating_sub(1)].push_str(&row);
         self.rows.remove(row_num);
+        if self.syntax.is_some() {
+            self.highlights.remove(row_num);
+        }
+        self.update_highlights(row_num.saturating_sub(1), row_num.saturating_sub(1));
         self.is_modified = true;
     }
+
+    /// Re-highlights the changed rows `from..=to` and keeps going while the
+    /// state carried into the following rows differs from the cached one.
+    fn update_highlights(&mut self, from: usize, to: usize) {
+        let Some(syntax) = self.syntax else {
+            return;
+        };
+
+        let mut state = from
+            .checked_sub(1)
+            .and_then(|prev| self.highlights.get(prev))
+            .map_or(HighlightState::Normal, |highlight| highlight.end_state);
+
+        for row_num in from..self.rows.len() {
+            let (tokens, end_state) = syntax.highlight_row(&self.rows[row_num], state);
+            let highlight = RowHighlight { tokens, end_state };
+            match self.highlights.get_mut(row_num) {
+                Some(cached) => {
+                    let unchanged = cached.end_state == end_state;
+                    *cached = highlight;
+                    if row_num >= to && unchanged {
+                        break;
+                    }
+                },
+                None => self.highlights.push(highlight),
+            }
+            state = end_state;
+        }
+    }
+}
+
+/// Asks `access(2)` whether the process may write to `path`.
+fn check_write_access(path: &Path) -> Result<(), io::Error> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    // SAFETY: `path` is a valid NUL-terminated string.
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_document_is_only_saved_when_forced() {
+        let root = std::env::temp_dir().join(format!("rte-read-only-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let file_path = root.join("file.txt").to_string_lossy().into_owned();
+        let copy_path = root.join("copy.txt").to_string_lossy().into_owned();
+        fs::write(&file_path, "on disk").unwrap();
+        let mut document = Document::from_text(&file_path, "buffer");
+        document.set_read_only(true);
+
+        assert!(document.save(false).is_err());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "on disk");
+        document.write_to(&copy_path).unwrap();
+        assert_eq!(fs::read_to_string(&copy_path).unwrap(), "buffer");
+        document.save(true).unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "buffer");
+
+        document.save_as(&copy_path).unwrap();
+        assert!(!document.is_read_only());
+        document.save(false).unwrap();
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn scratch_document_is_never_saved_or_checked_on_disk() {
+        let file_path = std::env::temp_dir()
+            .join(format!("rte-scratch-{}.diff", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&file_path, "on disk").unwrap();
+        let mut document = Document::scratch(&file_path, vec![String::from("generated")]);
+
+        assert!(document.is_read_only());
+        assert!(document.save(true).is_err());
+        assert!(document.reload().is_err());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "on disk");
+        fs::write(&file_path, "changed").unwrap();
+        assert!(!document.changed_on_disk());
+        assert!(!document.poll_disk_change());
+        fs::remove_file(file_path).unwrap();
+    }
+
+    #[test]
+    fn files_without_write_permission_are_not_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join(format!("rte-writable-{}", std::process::id()));
+        let locked_dir = root.join("locked");
+        fs::create_dir_all(&locked_dir).unwrap();
+        let file_path = root.join("file.txt");
+        fs::write(&file_path, "").unwrap();
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o444)).unwrap();
+        fs::set_permissions(&locked_dir, fs::Permissions::from_mode(0o555)).unwrap();
+        let writable = |path: &Path| Document::from_text(&path.to_string_lossy(), "").is_writable();
+
+        // Permission bits don't apply to root.
+        // SAFETY: `geteuid` has no preconditions.
+        let is_root = unsafe { libc::geteuid() } == 0;
+        assert_eq!(writable(&file_path), is_root);
+        assert_eq!(writable(&locked_dir.join("new.txt")), is_root);
+        assert!(writable(&root.join("new.txt")));
+        assert!(!writable(&root.join("missing").join("new.txt")));
+
+        fs::set_permissions(&locked_dir, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(root).unwrap();
+    }
 }
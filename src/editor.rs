@@ -1,234 +1,2238 @@
+use crate::buffer::{self, Buffer, Position};
+use crate::completion;
+use crate::config::Config;
 use crate::document::Document;
-use crate::terminal::{InputEvent, KeyEvent, SyscallEvent, Terminal, TerminalEvent};
-use std::error;
-use std::fmt;
-use std::io;
-
-const INFO_MESSAGE: &str = "CTRL-Q = exit | CTRL-S = save";
-const STATUS_BG_COLOR: (u8, u8, u8) = (239, 239, 239);
-const STATUS_FG_COLOR: (u8, u8, u8) = (63, 63, 63);
-const DEFAULT_X_POSITION: usize = usize::MIN;
-const DEFAULT_Y_POSITION: usize = usize::MIN;
-
-#[derive(Default)]
-struct Position {
-    x: usize,
-    y: usize,
+use crate::error;
+use crate::highlighting::TokenType;
+use crate::layout::{self, FocusDirection, Layout, SplitDirection, Window};
+use crate::prompt::{Prompt, PromptKind};
+use crate::status_line::{self, StatusInfo};
+use crate::terminal::{
+    self, InputEvent, KeyEvent, MouseButton, MouseEvent, MouseEventKind, Rect, SyscallEvent,
+    Terminal, TerminalEvent, Timer,
+};
+use crate::theme::{Theme, ThemeElement};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const INFO_MESSAGE: &str = "F1 help | ^Q exit | ^S save | ^O open | ^G go to | ^W close";
+/// Shown by F1 in a read-only buffer named `HELP_BUFFER_NAME`.
+const HELP_LINES: [&str; 22] = [
+    "Ctrl-Q      exit",
+    "Ctrl-S      save",
+    "Ctrl-A      save as",
+    "Alt-w       write a copy",
+    "Alt-i       insert a file",
+    "Ctrl-O      open a file",
+    "Ctrl-G      go to line[:column], +N, -N or N%",
+    "Ctrl-B      jump to the matching bracket",
+    "Ctrl-N      next buffer",
+    "Ctrl-P      previous buffer",
+    "Ctrl-L      list buffers",
+    "Ctrl-W      close the buffer",
+    "Alt-s       split horizontally",
+    "Alt-v       split vertically",
+    "Alt-x       close the window",
+    "Alt-h/j/k/l focus the window to the left, below, above or right",
+    "Alt-+/Alt-- grow or shrink the window",
+    "Ctrl-T      switch the theme",
+    "Insert      toggle overwrite mode",
+    "Ctrl-Z      suspend",
+    "Tab         complete a path in file prompts",
+    "F1          this help",
+];
+const HELP_BUFFER_NAME: &str = "[help]";
+const GOTO_PROMPT: &str = "Go to [line[:col] | +N | -N | N%]: ";
+const OPEN_FILE_PROMPT: &str = "Open file: ";
+const SAVE_AS_PROMPT: &str = "Save as: ";
+const WRITE_COPY_PROMPT: &str = "Write copy to: ";
+const INSERT_FILE_PROMPT: &str = "Insert file: ";
+const FORCE_SAVE_PROMPT: &str = "Buffer is read-only, save anyway? (y/N): ";
+const READ_ONLY_MESSAGE: &str = "Buffer is read-only";
+const SCRATCH_SAVE_MESSAGE: &str = "Buffer has no file, use save as";
+const NOT_WRITABLE_MESSAGE: &str = "Not writable, opened read-only:";
+const CLOSE_BUFFER_PROMPT: &str = "Buffer has unsaved changes, close anyway? (y/N): ";
+const THEME_PROMPT: &str = "Theme (empty to list): ";
+const FILE_CHANGED_PROMPT: &str = "File changed on disk: (r)eload, (o)verwrite, (d)iff? ";
+const WINDOW_SEPARATOR: &str = "\u{2502}";
+const WINDOW_RESIZE_STEP: i16 = 5;
+const MIN_WINDOW_HEIGHT: u16 = 2;
+const MIN_WINDOW_WIDTH: u16 = 1;
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const MOUSE_SCROLL_LINES: isize = 3;
+const RECOVERY_FILE_SUFFIX: &str = ".rte-save";
+const STATUS_MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+const DIFF_FILE_SUFFIX: &str = ".diff";
+const WINDOW_TITLE_PREFIX: &str = concat!(env!("CARGO_PKG_NAME"), " \u{2014} ");
+const MODIFIED_TITLE_FLAG: &str = "[+] ";
+
+/// What is highlighted in a row of a window, in columns of the window.
+/// A screen cell of a text row. The second cell of a double-width character
+/// has no symbol of its own.
+#[derive(Debug, Clone, Copy)]
+struct RowCell {
+    symbol: Option<char>,
+    token: TokenType,
 }
 
-impl fmt::Display for Position {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "({}|{})", self.x, self.y)
+impl Default for RowCell {
+    fn default() -> Self {
+        RowCell {
+            symbol: Some(' '),
+            token: TokenType::Normal,
+        }
     }
 }
 
+struct RowMarks {
+    /// The selected columns, `to` exclusive.
+    selected_range: Option<(usize, usize)>,
+    matching_bracket: Option<usize>,
+    cursor_line: bool,
+    ruler: Option<usize>,
+}
+
 pub struct Editor {
     exit: bool,
     terminal: Terminal,
-    document: Document,
-    cursor_position: Position,
-    screen_offset: Position,
+    buffers: Vec<Buffer>,
+    windows: BTreeMap<usize, Window>,
+    layout: Layout,
+    focused_window: usize,
+    next_window_id: usize,
+    prompt: Option<Prompt>,
+    status_message: Option<String>,
+    /// Counts the status messages shown, so an expiry timer only clears the
+    /// message it was scheduled for.
+    status_message_serial: u64,
+    last_click: Option<(Instant, u16, u16)>,
+    terminated_by: Option<i32>,
+    theme: Theme,
+    config: Config,
+    /// Whether typed characters replace the ones under the cursor.
+    overwrite: bool,
+    /// Whether a `Timer::DiskCheck` is pending, so only one is scheduled.
+    disk_check_scheduled: bool,
 }
 
 impl Editor {
-    pub fn new(terminal: Terminal, document: Document) -> Self {
+    pub fn new(terminal: Terminal, documents: Vec<Document>, theme: Theme, config: Config) -> Self {
+        let first_window = Window {
+            buffer_index: usize::MIN,
+            cursor_position: Position::default(),
+            screen_offset: Position::default(),
+        };
+
         Editor {
             exit: bool::default(),
             terminal,
-            document,
-            cursor_position: Position::default(),
-            screen_offset: Position::default(),
+            buffers: documents.into_iter().map(Buffer::new).collect(),
+            windows: BTreeMap::from([(usize::MIN, first_window)]),
+            layout: Layout::Window(usize::MIN),
+            focused_window: usize::MIN,
+            next_window_id: 1,
+            prompt: None,
+            status_message: None,
+            status_message_serial: u64::MIN,
+            last_click: None,
+            terminated_by: None,
+            theme,
+            config,
+            overwrite: false,
+            disk_check_scheduled: false,
         }
     }
 
-    pub fn run(&mut self) -> Result<(), Box<dyn error::Error>> {
+    pub fn run(&mut self) -> error::Result<()> {
+        if self.config.status_line.shows_clock() {
+            self.terminal
+                .schedule_timer(Timer::ClockTick, status_line::until_next_minute());
+        }
+        self.schedule_disk_check();
+        let not_writable: Vec<&str> = self
+            .buffers
+            .iter()
+            .filter(|buffer| !buffer.document.is_writable())
+            .map(|buffer| buffer.document.file_path.as_str())
+            .collect();
+        if !not_writable.is_empty() {
+            self.show_message(format!(
+                "{NOT_WRITABLE_MESSAGE} {}",
+                not_writable.join(", ")
+            ));
+        }
         self.render()?;
-        while !self.exit {
-            self.process_event()?;
-            self.render()?;
+        loop {
+            let event = self.terminal.pull_event()?;
+            let mut changed = self.process_event(event)?;
+            // Handle everything that queued up meanwhile before drawing, so a
+            // paste or key repeat is rendered once instead of per key.
+            while !self.exit {
+                let Some(event) = self.terminal.try_pull_event() else {
+                    break;
+                };
+                changed |= self.process_event(event)?;
+            }
+            if self.exit {
+                break;
+            }
+            self.schedule_disk_check();
+            if changed {
+                self.render()?;
+            }
         }
         Ok(())
     }
 
-    fn render(&mut self) -> Result<(), io::Error> {
-        Terminal::cursor_hide();
-        Terminal::cursor_to_default_position();
-
-        self.change_offsets();
-        self.render_rows();
-        self.render_status_bar();
+    /// Returns the signal that made the editor exit, if any.
+    pub fn terminated_by(&self) -> Option<i32> {
+        self.terminated_by
+    }
 
-        Terminal::cursor_to_position(
-            self.cursor_position.x.saturating_sub(self.screen_offset.x) as u16,
-            self.cursor_position.y.saturating_sub(self.screen_offset.y) as u16,
+    /// Places the cursor of the buffer at `buffer_index` at a 1-based `line`
+    /// and character `column`, clamped to the document.
+    pub fn goto_location(&mut self, buffer_index: usize, line: usize, column: usize) {
+        self.goto(
+            buffer_index,
+            line.saturating_sub(1),
+            column.saturating_sub(1),
         );
-        Terminal::cursor_show();
-
-        self.terminal.flush()
     }
 
-    fn render_rows(&self) {
-        for row_num in 0..self.terminal.height() {
-            Terminal::clear_current_line();
-            if let Some(row) = self
-                .document
-                .try_get_row(self.screen_offset.y.saturating_add(row_num as usize))
-            {
-                self.render_row(row);
-            } else {
-                println!("\r");
+    fn render(&mut self) -> error::Result<()> {
+        self.terminal.cursor_hide();
+        if self.config.window_title {
+            let title = self.window_title();
+            self.terminal.set_title(&title);
+        }
+
+        let (windows, separators) = self.arrange_windows();
+        self.change_offsets(&windows);
+        for &(window_id, area) in &windows {
+            self.render_window(window_id, area);
+        }
+        self.terminal
+            .set_style(self.theme.style(ThemeElement::Text));
+        for &separator in &separators {
+            for row in 0..separator.height {
+                self.terminal
+                    .print_in_area(separator, row, WINDOW_SEPARATOR);
             }
         }
+        self.terminal.reset_style();
+        self.render_message_line();
+
+        let cursor_shape = if self.prompt.is_some() {
+            self.config.prompt_cursor_shape
+        } else if self.overwrite {
+            self.config.overwrite_cursor_shape
+        } else {
+            self.config.cursor_shape
+        };
+        self.terminal.set_cursor_shape(cursor_shape);
+        if let Some(prompt) = &self.prompt {
+            let prompt_x = prompt.cursor_x() as u16;
+            self.terminal
+                .cursor_to_position(prompt_x, self.message_line_y());
+        } else if let Some(&(_, area)) = windows
+            .iter()
+            .find(|(window_id, _)| *window_id == self.focused_window)
+        {
+            let buffer = self.buffer();
+            let Position { x, y } = buffer.cursor_position;
+            let cursor_x = buffer
+                .document
+                .display_column(y, x)
+                .saturating_sub(buffer.screen_offset.x) as u16;
+            let cursor_y = buffer
+                .cursor_position
+                .y
+                .saturating_sub(buffer.screen_offset.y) as u16;
+            self.terminal.cursor_to_position(
+                area.x.saturating_add(cursor_x),
+                area.y.saturating_add(cursor_y),
+            );
+        }
+        self.terminal.cursor_show();
+
+        self.terminal.flush()
     }
 
-    fn render_row(&self, row: &str) {
-        let start = self.screen_offset.x;
-        let end = start.saturating_add(self.terminal.width() as usize);
-        let render_target: String = row.chars().skip(start).take(end - start).collect();
-        println!("{render_target}\r");
+    /// Names the document of the focused window, like `rte — [+] main.rs`.
+    fn window_title(&self) -> String {
+        let document = &self.buffer().document;
+        let flag = if document.is_modified() {
+            MODIFIED_TITLE_FLAG
+        } else {
+            ""
+        };
+        format!("{WINDOW_TITLE_PREFIX}{flag}{}", document.file_name())
     }
 
-    fn render_status_bar(&self) {
-        Terminal::clear_current_line();
+    fn render_window(&mut self, window_id: usize, area: Rect) {
+        let buffer_index = self.windows[&window_id].buffer_index;
+        let (cursor_position, screen_offset) = self.window_view(window_id);
+        let (matching_bracket, selection) = if window_id == self.focused_window {
+            let buffer = self.buffer();
+            (buffer.matching_bracket_position(), buffer.selection())
+        } else {
+            (None, None)
+        };
+        let document = &self.buffers[buffer_index].document;
+        // Positions are byte indexes, the marks are laid out in screen columns.
+        let column = |position: Position| document.display_column(position.y, position.x);
+        let matching_bracket = matching_bracket.map(|position| Position {
+            x: column(position),
+            ..position
+        });
+        let selection = selection.map(|(start, end)| {
+            (
+                Position {
+                    x: column(start),
+                    ..start
+                },
+                Position {
+                    x: column(end),
+                    ..end
+                },
+            )
+        });
 
-        let mut document_is_modified_flag = "";
-        if self.document.is_modified() {
-            document_is_modified_flag = "[+] ";
+        let text_height = area.height.saturating_sub(1);
+        for row in 0..text_height {
+            let document_row_num = screen_offset.y.saturating_add(row as usize);
+            let document = &self.buffers[buffer_index].document;
+            let cells = row_cells(
+                document,
+                document_row_num,
+                screen_offset.x,
+                area.width as usize,
+            );
+            let highlighted_index = matching_bracket
+                .filter(|position| position.y == document_row_num)
+                .and_then(|position| position.x.checked_sub(screen_offset.x));
+            let selected_range = selection
+                .and_then(|(start, end)| {
+                    if document_row_num < start.y || document_row_num > end.y {
+                        return None;
+                    }
+                    let row_len = document.try_get_row(document_row_num)?.len();
+                    let from = if document_row_num == start.y {
+                        start.x
+                    } else {
+                        0
+                    };
+                    // The line break of a selected line is shown as one cell.
+                    let to = if document_row_num == end.y {
+                        end.x
+                    } else {
+                        document.display_column(document_row_num, row_len) + 1
+                    };
+                    Some((from, to))
+                })
+                .map(|(from, to)| {
+                    (
+                        from.saturating_sub(screen_offset.x),
+                        to.saturating_sub(screen_offset.x).min(area.width as usize),
+                    )
+                })
+                .filter(|(from, to)| from < to);
+            let marks = RowMarks {
+                selected_range,
+                matching_bracket: highlighted_index,
+                cursor_line: self.config.cursor_line && document_row_num == cursor_position.y,
+                ruler: self
+                    .config
+                    .ruler
+                    .and_then(|ruler| ruler.checked_sub(screen_offset.x)),
+            };
+            self.render_row(area, row, &cells, &marks);
         }
 
-        let status_message = format!(
-            "{}{} {}",
-            document_is_modified_flag, self.document.file_path, self.cursor_position
-        );
-        let end_spaces = " ".repeat(
-            self.terminal
-                .width()
-                .saturating_sub(status_message.len() as u16) as usize,
-        );
-        let status = format!("{status_message}{end_spaces}");
+        self.render_status_bar(area, text_height, window_id, cursor_position);
+    }
+
+    /// Draws a text row, layering the cursor line, the ruler, the syntax
+    /// highlighting, the selection and the matching bracket in that order.
+    fn render_row(&mut self, area: Rect, row: u16, cells: &[RowCell], marks: &RowMarks) {
+        let base = self.theme.style(if marks.cursor_line {
+            ThemeElement::CursorLine
+        } else {
+            ThemeElement::Text
+        });
+        let mut styles = vec![base; cells.len()];
+        if let Some(style) = marks.ruler.and_then(|ruler| styles.get_mut(ruler)) {
+            *style = self.theme.style_over(ThemeElement::Ruler, *style);
+        }
+        for (style, cell) in styles.iter_mut().zip(cells) {
+            if cell.token != TokenType::Normal {
+                *style = self
+                    .theme
+                    .style_over(ThemeElement::Syntax(cell.token), *style);
+            }
+        }
+        if let Some((from, to)) = marks.selected_range {
+            for style in &mut styles[from..to] {
+                *style = self.theme.style_over(ThemeElement::Selection, *style);
+            }
+        }
+        if let Some(style) = marks
+            .matching_bracket
+            .and_then(|index| styles.get_mut(index))
+        {
+            *style = self.theme.style_over(ThemeElement::MatchingBracket, *style);
+        }
+        // The second half of a double-width character is drawn with the first.
+        for index in 1..cells.len() {
+            if cells[index].symbol.is_none() {
+                styles[index] = styles[index - 1];
+            }
+        }
 
-        Terminal::set_row_color(STATUS_BG_COLOR, STATUS_FG_COLOR);
-        println!("{status}\r");
-        Terminal::reset_line_color();
+        let mut from = 0;
+        while from < styles.len() {
+            let style = styles[from];
+            let to = from
+                + styles[from..]
+                    .iter()
+                    .take_while(|other| **other == style)
+                    .count();
+            let text: String = cells[from..to]
+                .iter()
+                .filter_map(|cell| cell.symbol)
+                .collect();
+            self.terminal.cursor_to_position(
+                area.x.saturating_add(from as u16),
+                area.y.saturating_add(row),
+            );
+            self.terminal.set_style(style);
+            self.terminal.print(&text);
+            from = to;
+        }
+        self.terminal.reset_style();
+    }
+
+    fn render_status_bar(
+        &mut self,
+        area: Rect,
+        row: u16,
+        window_id: usize,
+        cursor_position: Position,
+    ) {
+        let buffer_index = self.windows[&window_id].buffer_index;
+        let buffer = &self.buffers[buffer_index];
+        let focused = window_id == self.focused_window;
+        let info = StatusInfo {
+            document: &buffer.document,
+            cursor_position,
+            selection: buffer.selection().filter(|_| focused),
+            buffer_index,
+            buffers_count: self.buffers.len(),
+            overwrite: self.overwrite,
+        };
+        let status = self.config.status_line.render(&info, area.width as usize);
 
-        Terminal::clear_current_line();
-        print!("{}\r", String::from(INFO_MESSAGE));
+        let element = if focused {
+            ThemeElement::StatusBar
+        } else {
+            ThemeElement::InactiveStatusBar
+        };
+        self.terminal.set_style(self.theme.style(element));
+        self.terminal.print_in_area(area, row, &status);
+        self.terminal.reset_style();
     }
 
-    fn process_event(&mut self) -> Result<(), Box<dyn error::Error>> {
-        match self.terminal.pull_event()? {
-            TerminalEvent::Input(input_event) => self.process_input_event(input_event)?,
-            TerminalEvent::Syscall(syscall_event) => self.process_syscall_event(&syscall_event)?,
-            TerminalEvent::Empty => (),
+    /// Draws the prompt, or else the status message or the key hints cut to
+    /// the width with an ellipsis. A prompt is only clipped, as its input and
+    /// the cursor come last.
+    fn render_message_line(&mut self) {
+        let width = self.terminal.width() as usize;
+        let message = if let Some(prompt) = &self.prompt {
+            prompt.text()
+        } else if let Some(status_message) = &self.status_message {
+            status_line::truncate(status_message, width)
+        } else {
+            status_line::truncate(INFO_MESSAGE, width)
+        };
+
+        let area = Rect {
+            x: u16::MIN,
+            y: self.message_line_y(),
+            width: self.terminal.width(),
+            height: 1,
+        };
+        self.terminal
+            .set_style(self.theme.style(ThemeElement::MessageLine));
+        self.terminal.print_in_area(area, 0, &message);
+        self.terminal.reset_style();
+    }
+
+    /// Applies `event` and returns whether the screen may have changed.
+    fn process_event(&mut self, event: TerminalEvent) -> error::Result<bool> {
+        match event {
+            TerminalEvent::Input(InputEvent::Key(key_event)) => {
+                self.process_key_event(key_event)?;
+                Ok(true)
+            },
+            TerminalEvent::Input(InputEvent::Mouse(mouse_event)) => {
+                self.process_mouse_event(mouse_event);
+                Ok(true)
+            },
+            TerminalEvent::Syscall(syscall_event) => {
+                self.process_syscall_event(&syscall_event)?;
+                Ok(true)
+            },
+            TerminalEvent::Timer(Timer::MessageExpiry(serial)) => {
+                let expired = serial == self.status_message_serial && self.status_message.is_some();
+                if expired {
+                    self.status_message = None;
+                }
+                Ok(expired)
+            },
+            TerminalEvent::Timer(Timer::ClockTick) => {
+                self.terminal
+                    .schedule_timer(Timer::ClockTick, status_line::until_next_minute());
+                Ok(true)
+            },
+            TerminalEvent::Timer(Timer::DiskCheck) => {
+                self.disk_check_scheduled = false;
+                Ok(self.check_disk_changes())
+            },
+            TerminalEvent::Input(_) => Ok(false),
         }
-        Ok(())
     }
 
-    fn process_syscall_event(&mut self, event: &SyscallEvent) -> Result<(), io::Error> {
+    fn process_syscall_event(&mut self, event: &SyscallEvent) -> error::Result<()> {
         match event {
             SyscallEvent::WindowSizeChanged => self.terminal.resize()?,
-            SyscallEvent::Unsupported => (),
+            SyscallEvent::Continued => self.terminal.resume()?,
+            SyscallEvent::Terminated(signal) => {
+                log::warn!("Terminated by signal {signal}");
+                self.emergency_save();
+                self.terminated_by = Some(*signal);
+                self.exit = true;
+            },
+        }
+        Ok(())
+    }
+
+    fn process_key_event(&mut self, key_event: KeyEvent) -> error::Result<()> {
+        self.status_message = None;
+        if self.prompt.is_some() {
+            self.process_prompt_key_event(key_event);
+            return Ok(());
+        }
+        self.buffer_mut().selection_anchor = None;
+        let edits = matches!(
+            key_event,
+            KeyEvent::Char(_) | KeyEvent::Backspace | KeyEvent::InsertFile
+        );
+        if edits && self.buffer().document.is_read_only() {
+            self.show_message(String::from(READ_ONLY_MESSAGE));
+            return Ok(());
+        }
+
+        match key_event {
+            KeyEvent::Char(c) => {
+                let overwrite = self.overwrite;
+                self.buffer_mut().add_char(c, overwrite);
+            },
+            KeyEvent::Exit => {
+                self.exit = true;
+            },
+            KeyEvent::SaveDocument => self.save_document(false),
+            KeyEvent::SaveAs => {
+                self.prompt = Some(Prompt::new(PromptKind::SaveAs, SAVE_AS_PROMPT));
+            },
+            KeyEvent::WriteCopy => {
+                self.prompt = Some(Prompt::new(PromptKind::WriteCopy, WRITE_COPY_PROMPT));
+            },
+            KeyEvent::InsertFile => {
+                self.prompt = Some(Prompt::new(PromptKind::InsertFile, INSERT_FILE_PROMPT));
+            },
+            KeyEvent::Backspace => self.buffer_mut().remove_char(),
+            KeyEvent::Up => self.buffer_mut().move_up(),
+            KeyEvent::Down => self.buffer_mut().move_down(),
+            KeyEvent::Left => self.buffer_mut().move_left(),
+            KeyEvent::Right => self.buffer_mut().move_right(),
+            KeyEvent::JumpToMatchingBracket => self.buffer_mut().jump_to_matching_bracket(),
+            KeyEvent::GoTo => {
+                self.prompt = Some(Prompt::new(PromptKind::GoTo, GOTO_PROMPT));
+            },
+            KeyEvent::OpenFile => {
+                self.prompt = Some(Prompt::new(PromptKind::OpenFile, OPEN_FILE_PROMPT));
+            },
+            KeyEvent::SwitchTheme => {
+                self.prompt = Some(Prompt::new(PromptKind::SwitchTheme, THEME_PROMPT));
+            },
+            KeyEvent::NextBuffer => self.switch_buffer(true),
+            KeyEvent::PreviousBuffer => self.switch_buffer(false),
+            KeyEvent::ListBuffers => self.list_buffers(),
+            KeyEvent::CloseBuffer => self.close_buffer(false),
+            KeyEvent::SplitHorizontal => self.split_window(SplitDirection::Horizontal),
+            KeyEvent::SplitVertical => self.split_window(SplitDirection::Vertical),
+            KeyEvent::CloseWindow => self.close_window(),
+            KeyEvent::GrowWindow => self.resize_window(WINDOW_RESIZE_STEP),
+            KeyEvent::ShrinkWindow => self.resize_window(-WINDOW_RESIZE_STEP),
+            KeyEvent::FocusLeftWindow => self.focus_neighbour_window(FocusDirection::Left),
+            KeyEvent::FocusRightWindow => self.focus_neighbour_window(FocusDirection::Right),
+            KeyEvent::FocusUpperWindow => self.focus_neighbour_window(FocusDirection::Up),
+            KeyEvent::FocusLowerWindow => self.focus_neighbour_window(FocusDirection::Down),
+            KeyEvent::Help => self.show_help(),
+            KeyEvent::ToggleOverwrite => self.overwrite = !self.overwrite,
+            KeyEvent::Suspend => self.terminal.suspend()?,
+            KeyEvent::Escape | KeyEvent::Unsupported => (),
         }
         Ok(())
     }
 
-    fn process_input_event(&mut self, event: InputEvent) -> Result<(), io::Error> {
-        if let InputEvent::Key(key_event) = event {
-            match key_event {
-                KeyEvent::Char(c) => self.add_char(c),
-                KeyEvent::Exit => {
-                    self.exit = true;
+    fn process_mouse_event(&mut self, mouse_event: MouseEvent) {
+        if self.prompt.is_some() {
+            return;
+        }
+
+        let MouseEvent { kind, x, y, .. } = mouse_event;
+        let (windows, _) = self.arrange_windows();
+        let target = windows.iter().copied().find(|(_, area)| {
+            (area.x..area.right()).contains(&x)
+                && (area.y..area.bottom().saturating_sub(1)).contains(&y)
+        });
+
+        match kind {
+            MouseEventKind::Press(MouseButton::Left) => {
+                let Some((window_id, area)) = target else {
+                    return;
+                };
+                if window_id != self.focused_window {
+                    self.store_focused_view();
+                    self.focused_window = window_id;
+                    self.load_window_view(window_id);
+                }
+
+                let position = self.screen_to_document(area, x, y);
+                let now = Instant::now();
+                let is_double_click = self.last_click.is_some_and(|(time, last_x, last_y)| {
+                    now.duration_since(time) <= DOUBLE_CLICK_INTERVAL && (last_x, last_y) == (x, y)
+                });
+                if is_double_click {
+                    self.buffer_mut().select_word(position);
+                    self.last_click = None;
+                    return;
+                }
+
+                let buffer = self.buffer_mut();
+                let anchor = if mouse_event.modifiers.shift {
+                    buffer.selection_anchor.unwrap_or(buffer.cursor_position)
+                } else {
+                    position
+                };
+                buffer.goto(position.y, position.x);
+                buffer.selection_anchor = Some(anchor);
+                self.last_click = Some((now, x, y));
+            },
+            MouseEventKind::Drag => {
+                let Some(&(_, area)) = windows
+                    .iter()
+                    .find(|(window_id, _)| *window_id == self.focused_window)
+                else {
+                    return;
+                };
+                let position = self.screen_to_document(area, x, y);
+                let buffer = self.buffer_mut();
+                if buffer.selection_anchor.is_none() {
+                    buffer.selection_anchor = Some(buffer.cursor_position);
+                }
+                buffer.goto(position.y, position.x);
+            },
+            MouseEventKind::Release => {
+                if self.buffer().selection().is_none() {
+                    self.buffer_mut().selection_anchor = None;
+                }
+            },
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let Some((window_id, area)) = target else {
+                    return;
+                };
+                let lines = if kind == MouseEventKind::ScrollUp {
+                    -MOUSE_SCROLL_LINES
+                } else {
+                    MOUSE_SCROLL_LINES
+                };
+                self.scroll_window(window_id, lines, area.height.saturating_sub(1) as usize);
+            },
+            MouseEventKind::Press(_) => (),
+        }
+    }
+
+    /// Translates a screen cell inside the text part of `area` to a document
+    /// position of the focused buffer, on the character shown in that cell.
+    /// Cells outside of the area are clamped to its edges, so dragging past
+    /// them keeps selecting.
+    fn screen_to_document(&self, area: Rect, x: u16, y: u16) -> Position {
+        let last_column = area.right().saturating_sub(1).max(area.x);
+        let last_row = area.bottom().saturating_sub(2).max(area.y);
+        let column = x.clamp(area.x, last_column) - area.x;
+        let row = y.clamp(area.y, last_row) - area.y;
+        let buffer = self.buffer();
+        let y = buffer
+            .screen_offset
+            .y
+            .saturating_add(row as usize)
+            .min(buffer.document.len().saturating_sub(1));
+        let column = buffer.screen_offset.x.saturating_add(column as usize);
+        Position {
+            x: buffer.document.display_index(y, column),
+            y,
+        }
+    }
+
+    fn scroll_window(&mut self, window_id: usize, lines: isize, height: usize) {
+        if window_id == self.focused_window {
+            self.buffer_mut().scroll(lines, height);
+            return;
+        }
+
+        if let Some(window) = self.windows.get_mut(&window_id) {
+            buffer::scroll_view(
+                &self.buffers[window.buffer_index].document,
+                &mut window.cursor_position,
+                &mut window.screen_offset,
+                lines,
+                height,
+            );
+        }
+    }
+
+    fn process_prompt_key_event(&mut self, key_event: KeyEvent) {
+        match key_event {
+            KeyEvent::Char('\n') => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.confirm_prompt(&prompt);
+                }
+            },
+            KeyEvent::Char('\t') => {
+                let base_dir = self.document_dir();
+                if let Some(prompt) = self
+                    .prompt
+                    .as_mut()
+                    .filter(|prompt| prompt.kind.asks_for_path())
+                {
+                    prompt.complete(|input| completion::candidates(input, &base_dir));
+                }
+            },
+            KeyEvent::Char(c) => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.insert_char(c);
+                }
+            },
+            KeyEvent::Backspace => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.remove_char();
+                }
+            },
+            KeyEvent::Escape => self.prompt = None,
+            KeyEvent::Exit => self.exit = true,
+            _ => (),
+        }
+    }
+
+    fn confirm_prompt(&mut self, prompt: &Prompt) {
+        match &prompt.kind {
+            PromptKind::GoTo => {
+                let buffer = self.buffer();
+                match parse_goto_target(
+                    prompt.input(),
+                    buffer.cursor_position.y,
+                    buffer.document.len(),
+                ) {
+                    Some((y, x)) => self.goto(self.active_buffer(), y, x),
+                    None => {
+                        self.show_message(format!("Invalid position: {}", prompt.input().trim()));
+                    },
+                }
+            },
+            PromptKind::OpenFile => {
+                if let Some(file_path) = self.prompt_path(prompt) {
+                    self.open_file(&file_path);
+                }
+            },
+            PromptKind::InsertFile => {
+                if let Some(file_path) = self.prompt_path(prompt) {
+                    self.insert_file(&file_path);
+                }
+            },
+            PromptKind::CloseBuffer => {
+                if prompt.input().trim().eq_ignore_ascii_case("y") {
+                    self.close_buffer(true);
+                }
+            },
+            PromptKind::ForceSave => {
+                if prompt.input().trim().eq_ignore_ascii_case("y") {
+                    self.save_document(true);
+                }
+            },
+            PromptKind::SwitchTheme => self.switch_theme(prompt.input().trim()),
+            PromptKind::FileChanged { force } => match prompt.input().trim() {
+                "r" | "R" => self.reload_document(),
+                "o" | "O" if !force && self.buffer().document.is_read_only() => {
+                    self.prompt = Some(Prompt::new(PromptKind::ForceSave, FORCE_SAVE_PROMPT));
                 },
-                KeyEvent::SaveDocument => self.document.save()?,
-                KeyEvent::Backspace => self.remove_char(),
-                KeyEvent::Up => self.move_up(),
-                KeyEvent::Down => self.move_down(),
-                KeyEvent::Left => self.move_left(),
-                KeyEvent::Right => self.move_right(),
-                KeyEvent::Unsupported => (),
+                "o" | "O" => self.write_document(*force),
+                "d" | "D" => self.show_disk_diff(),
+                _ => (),
+            },
+            PromptKind::SaveAs | PromptKind::WriteCopy => {
+                if let Some(file_path) = self.prompt_path(prompt) {
+                    self.save_to(&file_path, prompt.kind == PromptKind::WriteCopy);
+                }
+            },
+            PromptKind::CreateDirectory { file_path, copy } => {
+                if prompt.input().trim().eq_ignore_ascii_case("y") {
+                    self.create_directory_and_save(file_path, *copy);
+                }
+            },
+        }
+    }
+
+    /// The path typed into `prompt`, relative to the directory of the active
+    /// document. `None` when nothing was typed.
+    fn prompt_path(&self, prompt: &Prompt) -> Option<String> {
+        let input = prompt.input().trim();
+        if input.is_empty() {
+            return None;
+        }
+        let path = completion::resolve(input, &self.document_dir());
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    /// The directory of the active document, where typed paths start from.
+    fn document_dir(&self) -> PathBuf {
+        Path::new(&self.buffer().document.file_path)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+    }
+
+    /// Shows `message` in the message bar until the next key or until it
+    /// expires.
+    fn show_message(&mut self, message: String) {
+        self.status_message = Some(message);
+        self.status_message_serial += 1;
+        self.terminal.schedule_timer(
+            Timer::MessageExpiry(self.status_message_serial),
+            STATUS_MESSAGE_TIMEOUT,
+        );
+    }
+
+    /// Loads and applies the theme `name`, or lists the available themes
+    /// when no name is given.
+    fn switch_theme(&mut self, name: &str) {
+        if name.is_empty() {
+            let themes = Theme::available().join(" | ");
+            self.show_message(format!("Themes: {themes} (current: {})", self.theme.name()));
+            return;
+        }
+        match Theme::load(name) {
+            Ok(theme) => self.theme = theme,
+            Err(err) => {
+                log::error!("{err}");
+                self.show_message(err.to_string());
+            },
+        }
+    }
+
+    /// Saves the active buffer, asking first when it is read-only, unless
+    /// `force` is set, or when its file changed on disk.
+    fn save_document(&mut self, force: bool) {
+        if self.buffer().document.is_scratch() {
+            self.show_message(String::from(SCRATCH_SAVE_MESSAGE));
+            return;
+        }
+        if !force && self.buffer().document.is_read_only() {
+            self.prompt = Some(Prompt::new(PromptKind::ForceSave, FORCE_SAVE_PROMPT));
+            return;
+        }
+        if self.buffer().document.changed_on_disk() {
+            let kind = PromptKind::FileChanged { force };
+            self.prompt = Some(Prompt::new(kind, FILE_CHANGED_PROMPT));
+            return;
+        }
+        self.write_document(force);
+    }
+
+    fn write_document(&mut self, force: bool) {
+        if let Err(err) = self.buffer_mut().document.save(force) {
+            log::error!("{err}");
+            self.show_message(format!("Can't save {err}"));
+        }
+    }
+
+    /// Writes the active buffer to `file_path`, either as its new path or as
+    /// a copy that leaves the buffer as it is. Asks first when the directory
+    /// of `file_path` does not exist.
+    fn save_to(&mut self, file_path: &str, copy: bool) {
+        if let Some(dir) = Path::new(file_path)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        {
+            let label = format!("Create directory {}? (y/N): ", dir.display());
+            let kind = PromptKind::CreateDirectory {
+                file_path: String::from(file_path),
+                copy,
+            };
+            self.prompt = Some(Prompt::new(kind, label));
+            return;
+        }
+
+        let document = &mut self.buffer_mut().document;
+        let result = if copy {
+            document.write_to(file_path)
+        } else {
+            document.save_as(file_path)
+        };
+        match result {
+            Ok(()) if copy => self.show_message(format!("Wrote a copy to {file_path}")),
+            Ok(()) => (),
+            Err(err) => {
+                log::error!("{err}");
+                self.show_message(format!("Can't save {err}"));
+            },
+        }
+    }
+
+    fn create_directory_and_save(&mut self, file_path: &str, copy: bool) {
+        let Some(dir) = Path::new(file_path).parent() else {
+            return;
+        };
+        if let Err(err) = fs::create_dir_all(dir) {
+            log::error!("Can't create {}: {err}", dir.display());
+            self.show_message(format!("Can't create directory {}: {err}", dir.display()));
+            return;
+        }
+        self.save_to(file_path, copy);
+    }
+
+    /// Schedules the next check for files changed on disk, unless one is
+    /// pending. Other programs writing a file send no notification without a
+    /// platform specific watcher, so the files are polled. To keep an idle
+    /// editor asleep, nothing is scheduled while no buffer has a file on disk
+    /// or a prompt is open, which includes the one asking about a change; the
+    /// main loop calls this again after the next events.
+    fn schedule_disk_check(&mut self) {
+        if self.disk_check_scheduled
+            || self.prompt.is_some()
+            || !self
+                .buffers
+                .iter()
+                .any(|buffer| buffer.document.is_on_disk())
+        {
+            return;
+        }
+        self.terminal
+            .schedule_timer(Timer::DiskCheck, DISK_CHECK_INTERVAL);
+        self.disk_check_scheduled = true;
+    }
+
+    /// Switches to the first buffer whose file changed on disk and asks what
+    /// to do about it. Scratch buffers have no file and are skipped. Returns
+    /// whether the screen needs a redraw.
+    fn check_disk_changes(&mut self) -> bool {
+        if self.prompt.is_some() {
+            return false;
+        }
+        let Some(buffer_index) = self
+            .buffers
+            .iter_mut()
+            .position(|buffer| !buffer.document.is_scratch() && buffer.document.poll_disk_change())
+        else {
+            return false;
+        };
+        self.set_active_buffer(buffer_index);
+        let kind = PromptKind::FileChanged { force: false };
+        self.prompt = Some(Prompt::new(kind, FILE_CHANGED_PROMPT));
+        true
+    }
+
+    fn reload_document(&mut self) {
+        let buffer = self.buffer_mut();
+        let result = buffer.document.reload();
+        let Position { x, y } = buffer.cursor_position;
+        buffer.goto(y, x);
+        if let Err(err) = result {
+            log::error!("{err}");
+            self.show_message(format!("Can't reload {err}"));
+        }
+    }
+
+    /// Opens the differences between the file on disk and the active buffer
+    /// in a new scratch buffer.
+    fn show_disk_diff(&mut self) {
+        let document = &self.buffer().document;
+        let diff_path = format!("{}{DIFF_FILE_SUFFIX}", document.file_path);
+        match document.diff_with_disk() {
+            Ok(diff) if diff.is_empty() => {
+                self.show_message(String::from("No differences from the file on disk"));
+            },
+            Ok(diff) => {
+                self.buffers
+                    .push(Buffer::new(Document::scratch(&diff_path, diff)));
+                self.set_active_buffer(self.buffers.len() - 1);
+            },
+            Err(err) => self.show_message(format!("Can't read {err}")),
+        }
+    }
+
+    /// Writes every modified buffer next to its file with the recovery
+    /// suffix, leaving the original files untouched.
+    fn emergency_save(&self) {
+        for buffer in self
+            .buffers
+            .iter()
+            .filter(|buffer| buffer.document.is_modified() && !buffer.document.is_scratch())
+        {
+            let recovery_path = format!("{}{RECOVERY_FILE_SUFFIX}", buffer.document.file_path);
+            match buffer.document.write_to(&recovery_path) {
+                Ok(()) => log::warn!("Unsaved changes written to {recovery_path}"),
+                Err(err) => log::error!("Can't write {recovery_path}: {err}"),
             }
         }
-        Ok(())
     }
 
-    fn add_char(&mut self, c: char) {
-        self.document
-            .insert_char(self.cursor_position.y, self.cursor_position.x, c);
-        self.move_right();
+    /// Moves the cursor to the 0-based row `y` and character `column`.
+    fn goto(&mut self, buffer_index: usize, y: usize, column: usize) {
+        let (width, height) = self.focused_view_size();
+        let buffer = &mut self.buffers[buffer_index];
+        let y = y.min(buffer.document.len().saturating_sub(1));
+        let x = buffer.document.column_index(y, column);
+        buffer.goto(y, x);
+        buffer.center_offsets(width, height);
+    }
+
+    fn open_file(&mut self, file_path: &str) {
+        if let Some(index) = self.buffers.iter().position(|buffer| {
+            !buffer.document.is_scratch() && buffer.document.file_path == file_path
+        }) {
+            self.set_active_buffer(index);
+            return;
+        }
+
+        match Document::new(file_path) {
+            Ok(document) => {
+                let writable = document.is_writable();
+                self.buffers.push(Buffer::new(document));
+                self.set_active_buffer(self.buffers.len() - 1);
+                if !writable {
+                    self.show_message(format!("{NOT_WRITABLE_MESSAGE} {file_path}"));
+                }
+            },
+            Err(err) => self.show_message(format!("Can't open {err}")),
+        }
     }
 
-    fn remove_char(&mut self) {
-        if self.cursor_position.x > DEFAULT_X_POSITION {
-            let prev_index = self.cursor_position.x.saturating_sub(1);
-            self.document
-                .remove_char(self.cursor_position.y, prev_index);
-            self.move_left();
-        } else if self.cursor_position.y > DEFAULT_Y_POSITION {
-            let current_row_num = self.cursor_position.y;
-            self.move_left();
-            self.document.join_row_with_previous(current_row_num);
+    /// Switches to the help buffer, opening it first if needed.
+    fn show_help(&mut self) {
+        if let Some(index) = self.buffers.iter().position(|buffer| {
+            buffer.document.is_scratch() && buffer.document.file_path == HELP_BUFFER_NAME
+        }) {
+            self.set_active_buffer(index);
+            return;
         }
+        let rows = HELP_LINES.iter().map(|line| String::from(*line)).collect();
+        self.buffers
+            .push(Buffer::new(Document::scratch(HELP_BUFFER_NAME, rows)));
+        self.set_active_buffer(self.buffers.len() - 1);
     }
 
-    fn move_up(&mut self) {
-        self.cursor_position.y = self.cursor_position.y.saturating_sub(1);
-        let row_len = self.document.get_row(self.cursor_position.y).len();
-        if self.cursor_position.x > row_len {
-            self.cursor_position.x = row_len;
+    /// Inserts the content of the file at `file_path` at the cursor.
+    fn insert_file(&mut self, file_path: &str) {
+        match fs::read_to_string(file_path) {
+            Ok(text) => self.buffer_mut().insert_text(&text),
+            Err(err) => {
+                let err = error::Error::document(file_path, err);
+                self.show_message(format!("Can't insert {err}"));
+            },
         }
     }
 
-    fn move_down(&mut self) {
-        if self.cursor_position.y < self.document.len() - 1 {
-            self.cursor_position.y = self.cursor_position.y.saturating_add(1);
-            let row_len = self.document.get_row(self.cursor_position.y).len();
-            if self.cursor_position.x > row_len {
-                self.cursor_position.x = row_len;
+    fn switch_buffer(&mut self, forward: bool) {
+        let buffers_count = self.buffers.len();
+        let active_buffer = self.active_buffer();
+        self.set_active_buffer(if forward {
+            (active_buffer + 1) % buffers_count
+        } else {
+            (active_buffer + buffers_count - 1) % buffers_count
+        });
+    }
+
+    fn list_buffers(&mut self) {
+        let active_buffer = self.active_buffer();
+        let list: Vec<String> = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| {
+                let active_flag = if index == active_buffer { "*" } else { "" };
+                let modified_flag = if buffer.document.is_modified() {
+                    "[+]"
+                } else {
+                    ""
+                };
+                format!(
+                    "{active_flag}{}:{modified_flag}{}",
+                    index + 1,
+                    buffer.document.file_path
+                )
+            })
+            .collect();
+        self.show_message(list.join(" | "));
+    }
+
+    fn close_buffer(&mut self, force: bool) {
+        if !force && self.buffer().document.is_modified() {
+            self.prompt = Some(Prompt::new(PromptKind::CloseBuffer, CLOSE_BUFFER_PROMPT));
+            return;
+        }
+
+        if self.buffers.len() == 1 {
+            self.exit = true;
+            return;
+        }
+
+        let closed_buffer = self.active_buffer();
+        self.buffers.remove(closed_buffer);
+        let replacement_buffer = closed_buffer.min(self.buffers.len() - 1);
+        for (window_id, window) in &mut self.windows {
+            if window.buffer_index == closed_buffer {
+                window.buffer_index = replacement_buffer;
+                if *window_id != self.focused_window {
+                    let buffer = &self.buffers[replacement_buffer];
+                    window.cursor_position = buffer.cursor_position;
+                    window.screen_offset = buffer.screen_offset;
+                }
+            } else if window.buffer_index > closed_buffer {
+                window.buffer_index -= 1;
             }
         }
     }
 
-    fn move_left(&mut self) {
-        if self.cursor_position.x == DEFAULT_X_POSITION
-            && self.cursor_position.y != DEFAULT_Y_POSITION
-        {
-            self.cursor_position.y = self.cursor_position.y.saturating_sub(1);
-            self.cursor_position.x = self.document.get_row(self.cursor_position.y).len();
+    fn split_window(&mut self, direction: SplitDirection) {
+        let (windows, _) = self.arrange_windows();
+        let Some(area) = windows
+            .iter()
+            .find(|(window_id, _)| *window_id == self.focused_window)
+            .map(|(_, area)| *area)
+        else {
+            return;
+        };
+
+        let has_room = match direction {
+            SplitDirection::Horizontal => area.height >= MIN_WINDOW_HEIGHT * 2,
+            SplitDirection::Vertical => area.width > MIN_WINDOW_WIDTH * 2,
+        };
+        if !has_room {
+            self.show_message(String::from("Not enough room to split the window"));
+            return;
+        }
+
+        self.store_focused_view();
+        let new_window = self.windows[&self.focused_window];
+        let new_window_id = self.next_window_id;
+        self.next_window_id += 1;
+        self.windows.insert(new_window_id, new_window);
+        self.layout
+            .split(self.focused_window, new_window_id, direction);
+        self.focused_window = new_window_id;
+    }
+
+    fn close_window(&mut self) {
+        let (windows, _) = self.arrange_windows();
+        let Some(closed_area) = windows
+            .iter()
+            .find(|(window_id, _)| *window_id == self.focused_window)
+            .map(|(_, area)| *area)
+        else {
+            return;
+        };
+
+        if !self.layout.remove(self.focused_window) {
+            self.show_message(String::from("Can't close the last window"));
+            return;
+        }
+        self.windows.remove(&self.focused_window);
+
+        let (windows, _) = self.arrange_windows();
+        let next_focus = windows
+            .iter()
+            .find(|(_, area)| {
+                (area.x..area.right()).contains(&closed_area.x)
+                    && (area.y..area.bottom()).contains(&closed_area.y)
+            })
+            .or_else(|| windows.first())
+            .map(|(window_id, _)| *window_id);
+        if let Some(window_id) = next_focus {
+            self.focused_window = window_id;
+            self.load_window_view(window_id);
+        }
+    }
+
+    fn resize_window(&mut self, delta: i16) {
+        self.layout.resize(self.focused_window, delta);
+    }
+
+    fn focus_neighbour_window(&mut self, direction: FocusDirection) {
+        let (windows, _) = self.arrange_windows();
+        let neighbour = windows
+            .iter()
+            .find(|(window_id, _)| *window_id == self.focused_window)
+            .and_then(|(_, area)| layout::find_neighbour(&windows, *area, direction));
+        if let Some(window_id) = neighbour {
+            self.store_focused_view();
+            self.focused_window = window_id;
+            self.load_window_view(window_id);
+        }
+    }
+
+    /// Saves the cursor and offsets of the focused window's buffer into the
+    /// window, so they can be restored when the window gets focus again. The
+    /// selection is only shown in the focused window and gets dropped.
+    fn store_focused_view(&mut self) {
+        let buffer_index = self.active_buffer();
+        let buffer = &mut self.buffers[buffer_index];
+        buffer.selection_anchor = None;
+        if let Some(window) = self.windows.get_mut(&self.focused_window) {
+            window.cursor_position = buffer.cursor_position;
+            window.screen_offset = buffer.screen_offset;
+        }
+    }
+
+    fn load_window_view(&mut self, window_id: usize) {
+        let window = self.windows[&window_id];
+        let buffer = &mut self.buffers[window.buffer_index];
+        buffer.goto(window.cursor_position.y, window.cursor_position.x);
+        buffer.screen_offset = window.screen_offset;
+    }
+
+    fn window_view(&self, window_id: usize) -> (Position, Position) {
+        if window_id == self.focused_window {
+            let buffer = self.buffer();
+            (buffer.cursor_position, buffer.screen_offset)
         } else {
-            self.cursor_position.x = self.cursor_position.x.saturating_sub(1);
+            let window = &self.windows[&window_id];
+            (window.cursor_position, window.screen_offset)
         }
     }
 
-    fn move_right(&mut self) {
-        if self.cursor_position.x < self.document.get_row(self.cursor_position.y).len() {
-            self.cursor_position.x = self.cursor_position.x.saturating_add(1);
-        } else if self.cursor_position.y < self.document.len() - 1 {
-            self.cursor_position.y = self.cursor_position.y.saturating_add(1);
-            self.cursor_position.x = DEFAULT_X_POSITION;
+    /// Keeps the cursor of every window visible. Unfocused windows may point
+    /// past the end of a document edited through another window, so their
+    /// cursors are clamped first.
+    fn change_offsets(&mut self, windows: &[(usize, Rect)]) {
+        for (window_id, area) in windows {
+            let width = area.width as usize;
+            let height = area.height.saturating_sub(1) as usize;
+            if *window_id == self.focused_window {
+                self.buffer_mut().change_offsets(width, height);
+                continue;
+            }
+
+            if let Some(window) = self.windows.get_mut(window_id) {
+                let document = &self.buffers[window.buffer_index].document;
+                let cursor_position = &mut window.cursor_position;
+                cursor_position.y = cursor_position.y.min(document.len().saturating_sub(1));
+                cursor_position.x = document.char_boundary(cursor_position.y, cursor_position.x);
+                buffer::scroll_to_cursor(
+                    document,
+                    *cursor_position,
+                    &mut window.screen_offset,
+                    width,
+                    height,
+                );
+            }
         }
     }
 
-    pub fn change_offsets(&mut self) {
-        let height = self.terminal.height() as usize;
-        if self.cursor_position.y < self.screen_offset.y {
-            self.screen_offset.y = self.cursor_position.y;
-        } else if self.cursor_position.y >= self.screen_offset.y.saturating_add(height) {
-            self.screen_offset.y = self
-                .cursor_position
-                .y
-                .saturating_sub(height)
-                .saturating_add(1);
+    fn arrange_windows(&self) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+        let area = Rect {
+            x: u16::MIN,
+            y: u16::MIN,
+            width: self.terminal.width(),
+            height: self.message_line_y(),
+        };
+        let mut windows = vec![];
+        let mut separators = vec![];
+        self.layout.arrange(area, &mut windows, &mut separators);
+        (windows, separators)
+    }
+
+    fn focused_view_size(&self) -> (usize, usize) {
+        let (windows, _) = self.arrange_windows();
+        windows
+            .iter()
+            .find(|(window_id, _)| *window_id == self.focused_window)
+            .map_or((0, 0), |(_, area)| {
+                (area.width as usize, area.height.saturating_sub(1) as usize)
+            })
+    }
+
+    fn message_line_y(&self) -> u16 {
+        self.terminal.height().saturating_add(1)
+    }
+
+    fn active_buffer(&self) -> usize {
+        self.windows[&self.focused_window].buffer_index
+    }
+
+    fn set_active_buffer(&mut self, buffer_index: usize) {
+        if let Some(window) = self.windows.get_mut(&self.focused_window) {
+            window.buffer_index = buffer_index;
         }
+    }
 
-        let width = self.terminal.width() as usize;
-        if self.cursor_position.x < self.screen_offset.x {
-            self.screen_offset.x = self.cursor_position.x;
-        } else if self.cursor_position.x >= self.screen_offset.x.saturating_add(width) {
-            self.screen_offset.x = self
-                .cursor_position
-                .x
-                .saturating_sub(width)
-                .saturating_add(1);
+    fn buffer(&self) -> &Buffer {
+        &self.buffers[self.active_buffer()]
+    }
+
+    fn buffer_mut(&mut self) -> &mut Buffer {
+        let buffer_index = self.active_buffer();
+        &mut self.buffers[buffer_index]
+    }
+}
+
+/// Lays out row `row_num` of `document` on `width` cells, starting at the
+/// screen column `first_column`. A double-width character cut by either edge
+/// is shown as a space, so the row never spills out of its window.
+fn row_cells(
+    document: &Document,
+    row_num: usize,
+    first_column: usize,
+    width: usize,
+) -> Vec<RowCell> {
+    let mut cells = vec![RowCell::default(); width];
+    let Some(row) = document.try_get_row(row_num) else {
+        return cells;
+    };
+    let last_column = first_column + width;
+    let mut column = 0;
+    for (index, symbol) in row.chars().enumerate() {
+        let start = column;
+        column += terminal::symbol_width(symbol);
+        if column <= first_column || start == column {
+            continue;
+        }
+        if start >= last_column {
+            break;
         }
+        let token = document.token_type(row_num, index);
+        let from = start.max(first_column) - first_column;
+        let to = column.min(last_column) - first_column;
+        let whole = start >= first_column && column <= last_column;
+        for (offset, cell) in cells[from..to].iter_mut().enumerate() {
+            let symbol = match offset {
+                _ if !whole => Some(' '),
+                0 => Some(symbol),
+                _ => None,
+            };
+            *cell = RowCell { symbol, token };
+        }
+    }
+    cells
+}
+
+/// Parses a goto target (`line`, `line:col`, `+N`, `-N` or `N%`, 1-based) into
+/// a 0-based `(row, column)` pair, where the column counts characters.
+/// Clamping is left to the caller.
+fn parse_goto_target(input: &str, current_row: usize, rows_count: usize) -> Option<(usize, usize)> {
+    let (line_part, column_part) = match input.trim().split_once(':') {
+        Some((line, column)) => (line.trim(), Some(column.trim())),
+        None => (input.trim(), None),
+    };
+
+    let row = if let Some(percent) = line_part.strip_suffix('%') {
+        let percent = percent.trim().parse::<usize>().ok()?.min(100);
+        rows_count.saturating_sub(1).saturating_mul(percent) / 100
+    } else if let Some(forward) = line_part.strip_prefix('+') {
+        current_row.saturating_add(forward.parse::<usize>().ok()?)
+    } else if let Some(backward) = line_part.strip_prefix('-') {
+        current_row.saturating_sub(backward.parse::<usize>().ok()?)
+    } else {
+        line_part.parse::<usize>().ok()?.saturating_sub(1)
+    };
+
+    let column = match column_part {
+        Some(column) => column.parse::<usize>().ok()?.saturating_sub(1),
+        None => 0,
+    };
+
+    Some((row, column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::test_backend::TestBackend;
+    use crate::terminal::{Color, ColorSupport, CursorShape, Modifiers};
+
+    const WIDTH: u16 = 40;
+    const HEIGHT: u16 = 10;
+    const STATUS_LINE: u16 = HEIGHT - 2;
+    const MESSAGE_LINE: u16 = HEIGHT - 1;
+
+    fn editor_with_documents(documents: Vec<Document>) -> (Editor, TestBackend) {
+        editor_with_config(documents, Config::default())
+    }
+
+    fn editor_with_config(documents: Vec<Document>, config: Config) -> (Editor, TestBackend) {
+        let backend = TestBackend::new(WIDTH, HEIGHT);
+        let terminal =
+            Terminal::with_backend(Box::new(backend.clone()), ColorSupport::TrueColor).unwrap();
+        (
+            Editor::new(terminal, documents, Theme::default(), config),
+            backend,
+        )
+    }
+
+    fn editor_with_text(text: &str) -> (Editor, TestBackend) {
+        editor_with_documents(vec![Document::from_text("test.rs", text)])
+    }
+
+    fn background(element: ThemeElement) -> Option<Color> {
+        Theme::default().style(element).background.map(Color::Rgb)
+    }
+
+    #[test]
+    fn renders_rows_and_status_bar() {
+        let (mut editor, backend) = editor_with_text("first\nsecond");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "first");
+        assert_eq!(backend.line(1), "second");
+        assert_eq!(backend.line(2), "");
+        assert_eq!(
+            backend.line(STATUS_LINE),
+            "test.rs                 rs 1:1 50% [1/1]"
+        );
+        assert_eq!(
+            backend.cell(0, STATUS_LINE).background,
+            background(ThemeElement::StatusBar)
+        );
+        assert_eq!(backend.cursor(), (0, 0));
+        assert!(backend.cursor_visible());
+    }
+
+    #[test]
+    fn queued_keys_are_rendered_once() {
+        let (mut editor, backend) = editor_with_text("first\nsecond");
+        backend.push_text("pasted ");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "pasted first");
+        // The first frame and one for all the queued keys.
+        assert_eq!(backend.flushes(), 2);
+    }
+
+    #[test]
+    fn typing_inserts_characters() {
+        let (mut editor, backend) = editor_with_text("first\nsecond");
+        backend.push_keys(&[KeyEvent::Down]);
+        backend.push_text("new ");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(1), "new second");
+        assert_eq!(backend.title().as_deref(), Some("rte \u{2014} [+] test.rs"));
+        assert_eq!(
+            backend.line(STATUS_LINE),
+            "[+] test.rs            rs 2:5 100% [1/1]"
+        );
+        assert_eq!(backend.cursor(), (4, 1));
+    }
+
+    #[test]
+    fn overwrite_mode_replaces_characters() {
+        let (mut editor, backend) = editor_with_text("abc\nsecond");
+        backend.push_keys(&[KeyEvent::Right, KeyEvent::ToggleOverwrite]);
+        backend.push_text("XYZ");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "aXYZ");
+        assert_eq!(backend.line(1), "second");
+        assert_eq!(
+            backend.line(STATUS_LINE),
+            "[+] test.rs         OVR rs 1:5 50% [1/1]"
+        );
+        assert_eq!(
+            backend.cursor_shape(),
+            CursorShape::Underline { blinking: false }
+        );
+
+        backend.push_keys(&[KeyEvent::ToggleOverwrite]);
+        backend.push_text("!");
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "aXYZ!");
+        assert_eq!(backend.cursor_shape(), CursorShape::Default);
+    }
+
+    #[test]
+    fn cursor_steps_over_whole_multibyte_characters() {
+        let (mut editor, backend) = editor_with_text("ab");
+        backend.push_text("\u{e9}\u{e9}");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "\u{e9}\u{e9}ab");
+        assert_eq!(backend.cursor(), (2, 0));
+        assert!(backend.line(STATUS_LINE).contains(" 1:3 "));
+
+        backend.push_keys(&[KeyEvent::Left, KeyEvent::Backspace, KeyEvent::Right]);
+        backend.push_keys(&[KeyEvent::Right]);
+        backend.push_text("\u{fc}");
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "\u{e9}a\u{fc}b");
+        assert_eq!(backend.cursor(), (3, 0));
+        assert!(backend.line(STATUS_LINE).contains(" 1:4 "));
+    }
+
+    #[test]
+    fn overwrite_mode_replaces_multibyte_characters() {
+        let (mut editor, backend) = editor_with_text("h\u{e9}llo");
+        backend.push_keys(&[KeyEvent::Right, KeyEvent::ToggleOverwrite]);
+        backend.push_text("e\u{fc}x");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "he\u{fc}xo");
+        assert_eq!(editor.buffer().cursor_position.x, "he\u{fc}x".len());
+    }
+
+    #[test]
+    fn help_key_opens_read_only_help_buffer() {
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_keys(&[KeyEvent::Help]);
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), HELP_LINES[0]);
+        assert!(editor.buffer().document.is_read_only());
+        assert_eq!(
+            backend.line(MESSAGE_LINE),
+            "F1 help | ^Q exit | ^S save | ^O open |\u{2026}"
+        );
+
+        backend.push_keys(&[KeyEvent::PreviousBuffer, KeyEvent::Help]);
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(editor.buffers.len(), 2);
+        assert_eq!(editor.active_buffer(), 1);
+    }
+
+    #[test]
+    fn goto_prompt_centers_target_line() {
+        let text: Vec<String> = (1..=100).map(|line| line.to_string()).collect();
+        let (mut editor, backend) = editor_with_text(&text.join("\n"));
+        backend.push_keys(&[KeyEvent::GoTo]);
+        backend.push_text("50:2\n");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "46");
+        assert_eq!(
+            backend.line(STATUS_LINE),
+            "test.rs                rs 50:2 50% [1/1]"
+        );
+        assert_eq!(backend.cursor(), (1, 4));
+    }
+
+    #[test]
+    fn goto_column_counts_characters() {
+        let (mut editor, backend) = editor_with_text("\u{e9}tat\nsecond");
+        backend.push_keys(&[KeyEvent::GoTo]);
+        backend.push_text("1:2\nx");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "\u{e9}xtat");
+
+        editor.goto_location(0, 1, 4);
+        backend.push_text("y");
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(backend.line(0), "\u{e9}xtyat");
+    }
+
+    #[test]
+    fn invalid_goto_target_shows_message() {
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_keys(&[KeyEvent::GoTo]);
+        backend.push_text("abc\n");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(MESSAGE_LINE), "Invalid position: abc");
+    }
+
+    #[test]
+    fn parses_goto_targets() {
+        assert_eq!(parse_goto_target("10", 0, 100), Some((9, 0)));
+        assert_eq!(parse_goto_target("10:4", 0, 100), Some((9, 3)));
+        assert_eq!(parse_goto_target("+5", 10, 100), Some((15, 0)));
+        assert_eq!(parse_goto_target("-20", 10, 100), Some((0, 0)));
+        assert_eq!(parse_goto_target("50%", 0, 101), Some((50, 0)));
+        assert_eq!(parse_goto_target("x", 0, 100), None);
+    }
+
+    #[test]
+    fn matching_bracket_skips_strings() {
+        let (mut editor, backend) = editor_with_text("f(\")\", [1])");
+        backend.push_keys(&[KeyEvent::Right]);
+        editor.run().unwrap();
+
+        assert_eq!(
+            backend.cell(10, 0).background,
+            background(ThemeElement::MatchingBracket)
+        );
+        assert_eq!(backend.cell(3, 0).background, None);
+
+        backend.push_keys(&[KeyEvent::JumpToMatchingBracket]);
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(backend.cursor(), (10, 0));
+        assert_eq!(
+            backend.cell(1, 0).background,
+            background(ThemeElement::MatchingBracket)
+        );
+    }
+
+    #[test]
+    fn matching_bracket_after_multibyte_character() {
+        let (mut editor, backend) = editor_with_text("\u{e9}(a)");
+        backend.push_keys(&[KeyEvent::Right, KeyEvent::JumpToMatchingBracket]);
+        editor.run().unwrap();
+
+        assert_eq!(backend.cursor(), (3, 0));
+        assert_eq!(
+            backend.cell(1, 0).background,
+            background(ThemeElement::MatchingBracket)
+        );
+
+        backend.push_keys(&[KeyEvent::JumpToMatchingBracket]);
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(backend.cursor(), (1, 0));
+    }
+
+    #[test]
+    fn highlights_cursor_line_and_ruler() {
+        let config = Config {
+            cursor_line: true,
+            ruler: Some(4),
+            cursor_shape: CursorShape::Bar { blinking: false },
+            prompt_cursor_shape: CursorShape::Underline { blinking: true },
+            ..Config::default()
+        };
+        let (mut editor, backend) = editor_with_config(
+            vec![Document::from_text("test.rs", "first\nsecond")],
+            config,
+        );
+        backend.push_keys(&[KeyEvent::Down, KeyEvent::GoTo]);
+        editor.run().unwrap();
+
+        let cursor_line = background(ThemeElement::CursorLine);
+        let ruler = background(ThemeElement::Ruler);
+        assert_eq!(backend.cell(0, 1).background, cursor_line);
+        assert_eq!(backend.cell(WIDTH - 1, 1).background, cursor_line);
+        assert_eq!(
+            backend.cell(0, 0).background,
+            background(ThemeElement::Text)
+        );
+        assert_eq!(backend.cell(4, 0).background, ruler);
+        assert_eq!(backend.cell(4, 1).background, ruler);
+        assert_eq!(
+            backend.cursor_shape(),
+            CursorShape::Underline { blinking: true }
+        );
+
+        backend.push_keys(&[KeyEvent::Escape]);
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(backend.cursor_shape(), CursorShape::Bar { blinking: false });
+    }
+
+    #[test]
+    fn switches_theme_live() {
+        let (mut editor, backend) = editor_with_text("\"text\"");
+        backend.push_keys(&[KeyEvent::SwitchTheme]);
+        backend.push_text("light\n");
+        editor.run().unwrap();
+
+        let light = Theme::load("light").unwrap();
+        let color = |element| light.style(element).background.map(Color::Rgb);
+        assert_eq!(
+            backend.cell(0, STATUS_LINE).background,
+            color(ThemeElement::StatusBar)
+        );
+        assert_eq!(
+            backend.cell(1, 0).foreground,
+            light
+                .style(ThemeElement::Syntax(TokenType::String))
+                .foreground
+                .map(Color::Rgb)
+        );
+        assert_eq!(backend.cell(1, 0).background, color(ThemeElement::Text));
+
+        backend.push_keys(&[KeyEvent::SwitchTheme]);
+        backend.push_text("missing\n");
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert!(backend
+            .line(MESSAGE_LINE)
+            .starts_with("invalid configuration: unknown theme"));
+        assert_eq!(editor.theme.name(), "light");
+    }
+
+    #[test]
+    fn switches_and_closes_buffers() {
+        let (mut editor, backend) = editor_with_documents(vec![
+            Document::from_text("a.txt", "a"),
+            Document::from_text("b.txt", "b"),
+        ]);
+        backend.push_keys(&[KeyEvent::NextBuffer]);
+        editor.run().unwrap();
+        assert_eq!(backend.line(0), "b");
+        assert_eq!(
+            backend.line(STATUS_LINE),
+            "b.txt                 txt 1:1 100% [2/2]"
+        );
+
+        backend.push_keys(&[KeyEvent::CloseBuffer]);
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(backend.line(0), "a");
+        assert_eq!(
+            backend.line(STATUS_LINE),
+            "a.txt                 txt 1:1 100% [1/1]"
+        );
+    }
+
+    #[test]
+    fn closing_modified_buffer_asks_for_confirmation() {
+        let (mut editor, backend) = editor_with_documents(vec![
+            Document::from_text("a.txt", "a"),
+            Document::from_text("b.txt", "b"),
+        ]);
+        backend.push_text("x");
+        backend.push_keys(&[KeyEvent::CloseBuffer]);
+        editor.run().unwrap();
+        assert!(CLOSE_BUFFER_PROMPT.starts_with(&backend.line(MESSAGE_LINE)));
+
+        editor.exit = false;
+        backend.push_text("y\n");
+        editor.run().unwrap();
+        assert_eq!(backend.line(0), "b");
+    }
+
+    #[test]
+    fn wide_characters_are_laid_out_by_display_width() {
+        let (mut editor, backend) = editor_with_text("\"\u{65e5}\u{672c}\" x\n");
+        backend.push_keys(&[KeyEvent::Right, KeyEvent::Right, KeyEvent::Right]);
+        editor.run().unwrap();
+
+        assert_eq!(backend.cell(1, 0).symbol, '\u{65e5}');
+        assert_eq!(backend.cell(3, 0).symbol, '\u{672c}');
+        assert_eq!(backend.cell(5, 0).symbol, '"');
+        assert_eq!(backend.cell(7, 0).symbol, 'x');
+        assert_eq!(
+            backend.cell(7, 0).foreground,
+            Theme::default()
+                .style(ThemeElement::Text)
+                .foreground
+                .map(Color::Rgb)
+        );
+        assert_eq!(backend.cursor(), (5, 0));
+
+        let (mut editor, backend) = editor_with_text(&"\u{65e5}".repeat(30));
+        backend.push_keys(&[KeyEvent::SplitVertical]);
+        editor.run().unwrap();
+
+        // 9 characters and a cut one fill the 19 columns of the left window.
+        assert_eq!(backend.cell(16, 0).symbol, '\u{65e5}');
+        assert_eq!(backend.cell(18, 0).symbol, ' ');
+        assert_eq!(backend.cell(19, 0).symbol, '\u{2502}');
+        assert_eq!(backend.cell(20, 0).symbol, '\u{65e5}');
+    }
+
+    #[test]
+    fn vertical_split_shows_same_buffer_twice() {
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_keys(&[KeyEvent::SplitVertical]);
+        backend.push_text("x");
+        editor.run().unwrap();
+
+        assert_eq!(backend.cell(19, 0).symbol, '\u{2502}');
+        assert!(backend.line(0).starts_with("xfirst"));
+        assert_eq!(&backend.line(0)[22..], "xfirst");
+        assert_eq!(backend.cursor(), (21, 0));
+        assert_eq!(
+            backend.cell(0, STATUS_LINE).background,
+            background(ThemeElement::InactiveStatusBar)
+        );
+        assert_eq!(
+            backend.cell(20, STATUS_LINE).background,
+            background(ThemeElement::StatusBar)
+        );
+    }
+
+    #[test]
+    fn window_size_change_relayouts_screen() {
+        let (mut editor, backend) = editor_with_text("first");
+        backend.resize(WIDTH, HEIGHT + 2);
+        backend.push_event(TerminalEvent::Syscall(SyscallEvent::WindowSizeChanged));
+        editor.run().unwrap();
+
+        assert_eq!(
+            backend.line(STATUS_LINE + 2),
+            "test.rs                rs 1:1 100% [1/1]"
+        );
+    }
+
+    #[test]
+    fn last_window_can_not_be_closed() {
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_keys(&[KeyEvent::CloseWindow]);
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(MESSAGE_LINE), "Can't close the last window");
+    }
+
+    #[test]
+    fn status_message_expires_with_its_timer() {
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_keys(&[KeyEvent::CloseWindow, KeyEvent::CloseWindow]);
+        // The timer of the first message must not clear the second one.
+        backend.push_event(TerminalEvent::Timer(Timer::MessageExpiry(1)));
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(MESSAGE_LINE), "Can't close the last window");
+        assert_eq!(
+            backend.scheduled_timers(),
+            [
+                (Timer::MessageExpiry(1), STATUS_MESSAGE_TIMEOUT),
+                (Timer::MessageExpiry(2), STATUS_MESSAGE_TIMEOUT),
+            ]
+        );
+
+        backend.push_event(TerminalEvent::Timer(Timer::MessageExpiry(2)));
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(
+            backend.line(MESSAGE_LINE),
+            status_line::truncate(INFO_MESSAGE, WIDTH as usize)
+        );
+    }
+
+    fn push_mouse(backend: &TestBackend, kind: MouseEventKind, x: u16, y: u16) {
+        backend.push_event(TerminalEvent::Input(InputEvent::Mouse(MouseEvent {
+            kind,
+            x,
+            y,
+            modifiers: Modifiers::default(),
+        })));
+    }
+
+    #[test]
+    fn click_places_cursor_and_drag_selects() {
+        let (mut editor, backend) = editor_with_text("first line\nsecond line");
+        push_mouse(&backend, MouseEventKind::Press(MouseButton::Left), 2, 0);
+        push_mouse(&backend, MouseEventKind::Drag, 3, 1);
+        push_mouse(&backend, MouseEventKind::Release, 3, 1);
+        editor.run().unwrap();
+
+        assert_eq!(
+            backend.line(STATUS_LINE),
+            "test.rs    12 selected rs 2:4 100% [1/1]"
+        );
+        assert_eq!(backend.cursor(), (3, 1));
+        assert_eq!(backend.cell(1, 0).background, None);
+        for (x, y) in [(2, 0), (10, 0), (0, 1), (2, 1)] {
+            assert_eq!(
+                backend.cell(x, y).background,
+                background(ThemeElement::Selection)
+            );
+        }
+        assert_eq!(backend.cell(11, 0).background, None);
+        assert_eq!(backend.cell(3, 1).background, None);
+    }
+
+    #[test]
+    fn double_click_selects_word() {
+        let (mut editor, backend) = editor_with_text("first line\nsecond line");
+        for _ in 0..2 {
+            push_mouse(&backend, MouseEventKind::Press(MouseButton::Left), 8, 1);
+            push_mouse(&backend, MouseEventKind::Release, 8, 1);
+        }
+        editor.run().unwrap();
+
+        assert_eq!(backend.cursor(), (11, 1));
+        assert_eq!(backend.cell(6, 1).background, None);
+        assert_eq!(
+            backend.cell(7, 1).background,
+            background(ThemeElement::Selection)
+        );
+        assert_eq!(
+            backend.cell(10, 1).background,
+            background(ThemeElement::Selection)
+        );
+    }
+
+    #[test]
+    fn clicks_land_on_character_boundaries() {
+        let (mut editor, backend) = editor_with_text("\u{e9}tat\n\u{65e5} word, next");
+        push_mouse(&backend, MouseEventKind::Press(MouseButton::Left), 1, 0);
+        push_mouse(&backend, MouseEventKind::Release, 1, 0);
+        backend.push_text("x");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "\u{e9}xtat");
+
+        for _ in 0..2 {
+            push_mouse(&backend, MouseEventKind::Press(MouseButton::Left), 4, 1);
+            push_mouse(&backend, MouseEventKind::Release, 4, 1);
+        }
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(backend.cursor(), (7, 1));
+        assert_eq!(backend.cell(2, 1).background, None);
+        assert_eq!(
+            backend.cell(3, 1).background,
+            background(ThemeElement::Selection)
+        );
+        assert_eq!(
+            backend.cell(6, 1).background,
+            background(ThemeElement::Selection)
+        );
+        assert_eq!(backend.cell(7, 1).background, None);
+
+        // A click on the second half of a wide character lands on it.
+        push_mouse(&backend, MouseEventKind::Press(MouseButton::Left), 1, 1);
+        push_mouse(&backend, MouseEventKind::Release, 1, 1);
+        backend.push_text("y");
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(backend.cell(0, 1).symbol, 'y');
+        assert_eq!(backend.cell(1, 1).symbol, '\u{65e5}');
+    }
+
+    #[test]
+    fn wheel_scrolls_view_and_keeps_cursor_inside() {
+        let text: Vec<String> = (1..=30).map(|line| line.to_string()).collect();
+        let (mut editor, backend) = editor_with_text(&text.join("\n"));
+        push_mouse(&backend, MouseEventKind::ScrollDown, 0, 0);
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "4");
+        assert_eq!(
+            backend.line(STATUS_LINE),
+            "test.rs                 rs 4:1 13% [1/1]"
+        );
+    }
+
+    #[test]
+    fn suspend_and_continue_redraw_whole_screen() {
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_keys(&[KeyEvent::Suspend]);
+        editor.run().unwrap();
+        assert!(backend.suspended());
+
+        let printed_cells = backend.printed_cells();
+        backend.push_event(TerminalEvent::Syscall(SyscallEvent::Continued));
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert!(!backend.suspended());
+        assert_eq!(
+            backend.printed_cells(),
+            printed_cells + usize::from(WIDTH * HEIGHT)
+        );
+        assert_eq!(backend.line(0), "first");
+    }
+
+    #[test]
+    fn termination_signal_saves_modified_buffers() {
+        let file_path = std::env::temp_dir()
+            .join(format!("rte-emergency-{}.txt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let (mut editor, backend) =
+            editor_with_documents(vec![Document::from_text(&file_path, "first")]);
+        backend.push_text("x");
+        backend.push_event(TerminalEvent::Syscall(SyscallEvent::Terminated(1)));
+        editor.run().unwrap();
+
+        let recovery_path = format!("{file_path}{RECOVERY_FILE_SUFFIX}");
+        assert_eq!(std::fs::read_to_string(&recovery_path).unwrap(), "xfirst");
+        assert!(!std::path::Path::new(&file_path).exists());
+        assert_eq!(editor.terminated_by(), Some(1));
+        std::fs::remove_file(recovery_path).unwrap();
+    }
+
+    #[test]
+    fn panic_restores_terminal() {
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_text("x");
+        backend.panic_after_events();
+        crate::terminal::install_panic_hook(backend.panic_restorer());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| editor.run()));
+        // Back to the default hook for the other tests.
+        drop(std::panic::take_hook());
+
+        assert!(result.is_err());
+        assert!(backend.restored_after_panic());
+        assert_eq!(backend.line(0), "xfirst");
+    }
+
+    #[test]
+    fn disk_check_is_only_scheduled_for_files_on_disk() {
+        let disk_checks = |backend: &TestBackend| {
+            backend
+                .scheduled_timers()
+                .iter()
+                .filter(|(timer, _)| *timer == Timer::DiskCheck)
+                .count()
+        };
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_text("x");
+        editor.run().unwrap();
+        assert_eq!(disk_checks(&backend), 0);
+
+        let file_path = std::env::temp_dir()
+            .join(format!("rte-disk-check-{}.txt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        std::fs::write(&file_path, "first").unwrap();
+        let (mut editor, backend) = editor_with_documents(vec![Document::new(&file_path).unwrap()]);
+        backend.push_text("x");
+        editor.run().unwrap();
+        assert_eq!(disk_checks(&backend), 1);
+
+        // The check is not rearmed until the prompt it opened is answered.
+        std::fs::write(&file_path, "changed").unwrap();
+        backend.push_event(TerminalEvent::Timer(Timer::DiskCheck));
+        editor.exit = false;
+        editor.run().unwrap();
+        assert!(editor.prompt.is_some());
+        assert_eq!(disk_checks(&backend), 1);
+
+        backend.push_text("r\n");
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(backend.line(0), "changed");
+        assert_eq!(disk_checks(&backend), 2);
+        std::fs::remove_file(file_path).unwrap();
+    }
+
+    #[test]
+    fn file_changed_on_disk_asks_before_saving() {
+        let file_path = std::env::temp_dir()
+            .join(format!("rte-changed-{}.txt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        std::fs::write(&file_path, "first\nsecond").unwrap();
+        let (mut editor, backend) = editor_with_documents(vec![Document::new(&file_path).unwrap()]);
+        std::fs::write(&file_path, "first\nchanged!").unwrap();
+        backend.push_text("x");
+        backend.push_keys(&[KeyEvent::SaveDocument]);
+        editor.run().unwrap();
+
+        assert!(FILE_CHANGED_PROMPT.starts_with(&backend.line(MESSAGE_LINE)));
+
+        backend.push_text("d\n");
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "first\nchanged!"
+        );
+        assert_eq!(backend.line(2), "@@ -1,2 +1,2 @@");
+        assert_eq!(backend.line(3), "-first");
+        assert_eq!(backend.line(6), "+second");
+
+        // The diff is only shown; saving it must not create a file.
+        backend.push_keys(&[KeyEvent::SaveDocument]);
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(backend.line(MESSAGE_LINE), SCRATCH_SAVE_MESSAGE);
+        assert!(!Path::new(&format!("{file_path}{DIFF_FILE_SUFFIX}")).exists());
+
+        backend.push_event(TerminalEvent::Timer(Timer::DiskCheck));
+        backend.push_text("r\n");
+        backend.push_event(TerminalEvent::Timer(Timer::DiskCheck));
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(editor.active_buffer(), 0);
+        assert_eq!(backend.line(0), "first");
+        assert_eq!(backend.line(1), "changed!");
+        assert!(!editor.buffer().document.is_modified());
+        assert!(editor.prompt.is_none());
+        std::fs::remove_file(file_path).unwrap();
+    }
+
+    #[test]
+    fn save_as_creates_missing_directory_and_write_copy_keeps_path() {
+        let root = std::env::temp_dir().join(format!("rte-save-as-{}", std::process::id()));
+        let file_path = format!("{}/new/dir/saved.rs", root.display());
+        let copy_path = format!("{}/copy.txt", root.display());
+        fs::create_dir_all(&root).unwrap();
+        let (mut editor, backend) =
+            editor_with_documents(vec![Document::from_rows("scratch", vec![String::new()])]);
+        backend.push_text("x");
+        backend.push_keys(&[KeyEvent::SaveAs]);
+        backend.push_text(&format!("{file_path}\n"));
+        editor.run().unwrap();
+
+        assert!(backend.line(MESSAGE_LINE).starts_with("Create directory "));
+        assert!(editor.buffer().document.is_modified());
+
+        backend.push_text("y\n");
+        backend.push_keys(&[KeyEvent::WriteCopy]);
+        backend.push_text(&format!("{copy_path}\n"));
+        editor.exit = false;
+        editor.run().unwrap();
+
+        let document = &editor.buffer().document;
+        assert_eq!(document.file_path, file_path);
+        assert_eq!(document.file_type(), Some("rs"));
+        assert!(!document.is_modified());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "x");
+        assert_eq!(fs::read_to_string(&copy_path).unwrap(), "x");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn save_as_prompt_completes_directories() {
+        let root =
+            std::env::temp_dir().join(format!("rte-save-as-complete-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("projects")).unwrap();
+        std::fs::write(root.join("notes.txt"), "").unwrap();
+        let (mut editor, backend) = editor_with_text("first");
+        backend.push_keys(&[KeyEvent::SaveAs]);
+        backend.push_text(&format!("{}/pro\t", root.display()));
+        editor.run().unwrap();
+
+        assert_eq!(
+            editor.prompt.unwrap().input(),
+            format!("{}/projects/", root.display())
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn insert_file_completes_relative_to_document() {
+        let root = std::env::temp_dir().join(format!("rte-insert-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("notes.txt"), "one\r\ntwo\r\n").unwrap();
+        fs::write(root.join("numbers.txt"), "").unwrap();
+        let file_path = format!("{}/main.rs", root.display());
+        let (mut editor, backend) =
+            editor_with_documents(vec![Document::from_text(&file_path, "[]")]);
+        backend.push_keys(&[KeyEvent::Right, KeyEvent::InsertFile]);
+        backend.push_text("n\t");
+        editor.run().unwrap();
+
+        assert_eq!(
+            backend.line(MESSAGE_LINE),
+            "Insert file: n  notes.txt  numbers.txt"
+        );
+
+        backend.push_text("\t\n");
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "[one");
+        assert_eq!(backend.line(1), "two");
+        assert_eq!(backend.line(2), "]");
+        assert_eq!(editor.buffer().cursor_position, Position { x: 0, y: 2 });
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn read_only_buffer_refuses_edits_and_saves_only_when_forced() {
+        let file_path = std::env::temp_dir()
+            .join(format!("rte-read-only-{}.txt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&file_path, "first").unwrap();
+        let mut document = Document::new(&file_path).unwrap();
+        document.set_read_only(true);
+        let (mut editor, backend) = editor_with_documents(vec![document]);
+        backend.push_text("x");
+        editor.run().unwrap();
+
+        assert_eq!(backend.line(0), "first");
+        assert_eq!(backend.line(MESSAGE_LINE), READ_ONLY_MESSAGE);
+        assert!(backend.line(STATUS_LINE).starts_with("[RO] "));
+
+        fs::write(&file_path, "changed").unwrap();
+        backend.push_keys(&[
+            KeyEvent::SaveDocument,
+            KeyEvent::Escape,
+            KeyEvent::SaveDocument,
+        ]);
+        backend.push_text("y\n");
+        backend.push_text("o\n");
+        editor.exit = false;
+        editor.run().unwrap();
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "first");
+        fs::remove_file(file_path).unwrap();
+    }
+
+    #[test]
+    fn overwriting_changed_file_of_read_only_buffer_asks_to_force() {
+        let file_path = std::env::temp_dir()
+            .join(format!("rte-read-only-changed-{}.txt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&file_path, "first").unwrap();
+        let mut document = Document::new(&file_path).unwrap();
+        document.set_read_only(true);
+        let (mut editor, backend) = editor_with_documents(vec![document]);
+        fs::write(&file_path, "changed").unwrap();
+        backend.push_event(TerminalEvent::Timer(Timer::DiskCheck));
+        backend.push_text("o\n");
+        editor.run().unwrap();
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "changed");
+        assert_eq!(backend.line(MESSAGE_LINE), FORCE_SAVE_PROMPT.trim_end());
+
+        backend.push_text("y\n");
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "changed");
+
+        backend.push_text("o\n");
+        editor.exit = false;
+        editor.run().unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "first");
+        fs::remove_file(file_path).unwrap();
+    }
+
+    #[test]
+    fn failed_save_is_reported_in_message_bar() {
+        let (mut editor, backend) =
+            editor_with_documents(vec![Document::from_text("/dev/null/f.txt", "first")]);
+        backend.push_text("x");
+        backend.push_keys(&[KeyEvent::SaveDocument]);
+        editor.run().unwrap();
+
+        assert!(backend
+            .line(MESSAGE_LINE)
+            .starts_with("Can't save /dev/null/f.txt: "));
+        assert!(backend.line(STATUS_LINE).starts_with("[+] "));
     }
 }
@@ -0,0 +1,87 @@
+use crossbeam::channel::{RecvError, SendError};
+use std::{error, fmt, io};
+
+const GENERAL_EXIT_CODE: i32 = 1;
+const CONFIG_EXIT_CODE: i32 = 2;
+const DOCUMENT_EXIT_CODE: i32 = 3;
+const TERMINAL_EXIT_CODE: i32 = 4;
+const CHANNEL_EXIT_CODE: i32 = 5;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Setting up, drawing to or restoring the terminal failed.
+    Terminal(io::Error),
+    /// Invalid command line arguments or logger setup.
+    Config(String),
+    /// An event handler thread went away.
+    Channel(String),
+    /// A document could not be read or written.
+    Document {
+        path: String,
+        source: io::Error,
+    },
+}
+
+impl Error {
+    pub fn document(path: &str, source: io::Error) -> Self {
+        Error::Document {
+            path: String::from(path),
+            source,
+        }
+    }
+
+    /// The process exit code reported when the error ends the editor.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) => GENERAL_EXIT_CODE,
+            Error::Config(_) => CONFIG_EXIT_CODE,
+            Error::Document { .. } => DOCUMENT_EXIT_CODE,
+            Error::Terminal(_) => TERMINAL_EXIT_CODE,
+            Error::Channel(_) => CHANNEL_EXIT_CODE,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {err}"),
+            Error::Terminal(err) => write!(f, "terminal error: {err}"),
+            Error::Config(message) => write!(f, "invalid configuration: {message}"),
+            Error::Channel(message) => write!(f, "event channel failed: {message}"),
+            Error::Document { path, source } => write!(f, "{path}: {source}"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) | Error::Terminal(err) | Error::Document { source: err, .. } => {
+                Some(err)
+            },
+            Error::Config(_) | Error::Channel(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<RecvError> for Error {
+    fn from(err: RecvError) -> Self {
+        Error::Channel(err.to_string())
+    }
+}
+
+impl<T> From<SendError<T>> for Error {
+    fn from(err: SendError<T>) -> Self {
+        Error::Channel(err.to_string())
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenType {
+    Normal,
+    String,
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightState {
+    Normal,
+    String(char),
+    BlockComment,
+}
+
+pub struct Syntax {
+    extensions: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    string_quotes: &'static [char],
+    char_quote: Option<char>,
+    multiline_strings: bool,
+}
+
+const SYNTAXES: &[Syntax] = &[
+    Syntax {
+        extensions: &["rs"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_quotes: &['"'],
+        char_quote: Some('\''),
+        multiline_strings: true,
+    },
+    Syntax {
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "java", "go", "cs"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_quotes: &['"'],
+        char_quote: Some('\''),
+        multiline_strings: false,
+    },
+    Syntax {
+        extensions: &["js", "jsx", "mjs", "ts", "tsx"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_quotes: &['"', '\'', '`'],
+        char_quote: None,
+        multiline_strings: true,
+    },
+    Syntax {
+        extensions: &["py"],
+        line_comment: Some("#"),
+        block_comment: None,
+        string_quotes: &['"', '\''],
+        char_quote: None,
+        multiline_strings: false,
+    },
+    Syntax {
+        extensions: &["sh", "bash", "zsh", "toml", "yaml", "yml"],
+        line_comment: Some("#"),
+        block_comment: None,
+        string_quotes: &['"', '\''],
+        char_quote: None,
+        multiline_strings: true,
+    },
+];
+
+impl Syntax {
+    pub fn for_file(file_path: &str) -> Option<&'static Syntax> {
+        let extension = Path::new(file_path).extension()?.to_str()?;
+        SYNTAXES
+            .iter()
+            .find(|syntax| syntax.extensions.contains(&extension))
+    }
+
+    pub fn highlight_row(
+        &self,
+        row: &str,
+        start_state: HighlightState,
+    ) -> (Vec<TokenType>, HighlightState) {
+        let chars: Vec<char> = row.chars().collect();
+        let mut tokens = Vec::with_capacity(chars.len());
+        let mut state = start_state;
+        let mut index = 0;
+
+        while index < chars.len() {
+            match state {
+                HighlightState::BlockComment => {
+                    let (_, end) = self.block_comment.unwrap_or_default();
+                    if starts_with_at(&chars, index, end) {
+                        push_tokens(&mut tokens, TokenType::Comment, end.chars().count());
+                        index += end.chars().count();
+                        state = HighlightState::Normal;
+                    } else {
+                        tokens.push(TokenType::Comment);
+                        index += 1;
+                    }
+                },
+                HighlightState::String(quote) => {
+                    tokens.push(TokenType::String);
+                    if chars[index] == '\\' && index + 1 < chars.len() {
+                        tokens.push(TokenType::String);
+                        index += 2;
+                    } else {
+                        if chars[index] == quote {
+                            state = HighlightState::Normal;
+                        }
+                        index += 1;
+                    }
+                },
+                HighlightState::Normal => {
+                    if let Some(line_comment) = self.line_comment {
+                        if starts_with_at(&chars, index, line_comment) {
+                            push_tokens(&mut tokens, TokenType::Comment, chars.len() - index);
+                            break;
+                        }
+                    }
+
+                    if let Some((start, _)) = self.block_comment {
+                        if starts_with_at(&chars, index, start) {
+                            push_tokens(&mut tokens, TokenType::Comment, start.chars().count());
+                            index += start.chars().count();
+                            state = HighlightState::BlockComment;
+                            continue;
+                        }
+                    }
+
+                    let c = chars[index];
+                    if self.char_quote == Some(c) {
+                        let literal_len = char_literal_len(&chars, index, c);
+                        if literal_len > 0 {
+                            push_tokens(&mut tokens, TokenType::String, literal_len);
+                            index += literal_len;
+                            continue;
+                        }
+                    }
+
+                    if self.string_quotes.contains(&c) {
+                        state = HighlightState::String(c);
+                        tokens.push(TokenType::String);
+                    } else {
+                        tokens.push(TokenType::Normal);
+                    }
+                    index += 1;
+                },
+            }
+        }
+
+        if let HighlightState::String(_) = state {
+            if !self.multiline_strings && !row.ends_with('\\') {
+                state = HighlightState::Normal;
+            }
+        }
+
+        (tokens, state)
+    }
+}
+
+fn starts_with_at(chars: &[char], index: usize, pattern: &str) -> bool {
+    let mut pattern_chars = pattern.chars();
+    let mut offset = index;
+    loop {
+        match pattern_chars.next() {
+            Some(p) if chars.get(offset) == Some(&p) => offset += 1,
+            Some(_) => return false,
+            None => return true,
+        }
+    }
+}
+
+fn push_tokens(tokens: &mut Vec<TokenType>, token_type: TokenType, count: usize) {
+    tokens.extend(std::iter::repeat_n(token_type, count));
+}
+
+/// Returns the length of a character literal such as `'a'` or `'\n'`
+/// starting at `index`, or zero when the quote opens something else
+/// (e.g. a Rust lifetime).
+fn char_literal_len(chars: &[char], index: usize, quote: char) -> usize {
+    match chars.get(index + 1) {
+        Some('\\') => chars
+            .iter()
+            .skip(index + 3)
+            .position(|c| *c == quote)
+            .map_or(0, |end| end + 4),
+        Some(_) if chars.get(index + 2) == Some(&quote) => 3,
+        _ => 0,
+    }
+}
@@ -0,0 +1,336 @@
+use crate::buffer::Position;
+use crate::terminal::Rect;
+
+const DEFAULT_SPLIT_RATIO: u16 = 50;
+const MIN_SPLIT_RATIO: u16 = 10;
+const MAX_SPLIT_RATIO: u16 = 90;
+const SEPARATOR_WIDTH: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// Windows are stacked on top of each other.
+    Horizontal,
+    /// Windows are placed side by side.
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// A view into one of the editor buffers. The cursor and scroll offsets are
+/// only kept up to date while the window is not focused; the focused window
+/// works directly on the state of its buffer.
+#[derive(Clone, Copy)]
+pub struct Window {
+    pub buffer_index: usize,
+    pub cursor_position: Position,
+    pub screen_offset: Position,
+}
+
+pub enum Layout {
+    Window(usize),
+    Split {
+        direction: SplitDirection,
+        ratio: u16,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+impl Layout {
+    pub fn split(&mut self, target: usize, new_window: usize, direction: SplitDirection) {
+        match self {
+            Layout::Window(window) if *window == target => {
+                *self = Layout::Split {
+                    direction,
+                    ratio: DEFAULT_SPLIT_RATIO,
+                    first: Box::new(Layout::Window(target)),
+                    second: Box::new(Layout::Window(new_window)),
+                };
+            },
+            Layout::Window(_) => (),
+            Layout::Split { first, second, .. } => {
+                first.split(target, new_window, direction);
+                second.split(target, new_window, direction);
+            },
+        }
+    }
+
+    /// Removes the `target` window and gives its space to the sibling.
+    /// The last remaining window can not be removed.
+    pub fn remove(&mut self, target: usize) -> bool {
+        let Layout::Split { first, second, .. } = self else {
+            return false;
+        };
+
+        let remaining = if matches!(**first, Layout::Window(window) if window == target) {
+            std::mem::replace(&mut **second, Layout::Window(target))
+        } else if matches!(**second, Layout::Window(window) if window == target) {
+            std::mem::replace(&mut **first, Layout::Window(target))
+        } else {
+            return first.remove(target) || second.remove(target);
+        };
+        *self = remaining;
+        true
+    }
+
+    /// Grows (positive `delta`) or shrinks the `target` window by changing the
+    /// ratio of the closest split that contains it.
+    pub fn resize(&mut self, target: usize, delta: i16) -> bool {
+        let Layout::Split {
+            ratio,
+            first,
+            second,
+            ..
+        } = self
+        else {
+            return false;
+        };
+
+        let delta = if first.contains(target) {
+            if first.resize(target, delta) {
+                return true;
+            }
+            delta
+        } else if second.contains(target) {
+            if second.resize(target, delta) {
+                return true;
+            }
+            -delta
+        } else {
+            return false;
+        };
+
+        *ratio = ratio
+            .saturating_add_signed(delta)
+            .clamp(MIN_SPLIT_RATIO, MAX_SPLIT_RATIO);
+        true
+    }
+
+    pub fn contains(&self, target: usize) -> bool {
+        match self {
+            Layout::Window(window) => *window == target,
+            Layout::Split { first, second, .. } => {
+                first.contains(target) || second.contains(target)
+            },
+        }
+    }
+
+    /// Splits `area` between the windows, collecting the window areas and the
+    /// one column wide separators between side by side windows.
+    pub fn arrange(
+        &self,
+        area: Rect,
+        windows: &mut Vec<(usize, Rect)>,
+        separators: &mut Vec<Rect>,
+    ) {
+        match self {
+            Layout::Window(window) => windows.push((*window, area)),
+            Layout::Split {
+                direction: SplitDirection::Horizontal,
+                ratio,
+                first,
+                second,
+            } => {
+                let first_height = split_length(area.height, *ratio);
+                first.arrange(
+                    Rect {
+                        height: first_height,
+                        ..area
+                    },
+                    windows,
+                    separators,
+                );
+                second.arrange(
+                    Rect {
+                        y: area.y.saturating_add(first_height),
+                        height: area.height.saturating_sub(first_height),
+                        ..area
+                    },
+                    windows,
+                    separators,
+                );
+            },
+            Layout::Split {
+                direction: SplitDirection::Vertical,
+                ratio,
+                first,
+                second,
+            } => {
+                let available_width = area.width.saturating_sub(SEPARATOR_WIDTH);
+                let first_width = split_length(available_width, *ratio);
+                first.arrange(
+                    Rect {
+                        width: first_width,
+                        ..area
+                    },
+                    windows,
+                    separators,
+                );
+                separators.push(Rect {
+                    x: area.x.saturating_add(first_width),
+                    width: SEPARATOR_WIDTH.min(area.width),
+                    ..area
+                });
+                second.arrange(
+                    Rect {
+                        x: area
+                            .x
+                            .saturating_add(first_width)
+                            .saturating_add(SEPARATOR_WIDTH),
+                        width: available_width.saturating_sub(first_width),
+                        ..area
+                    },
+                    windows,
+                    separators,
+                );
+            },
+        }
+    }
+}
+
+/// Picks the window closest to `from` in the given direction, preferring
+/// windows that overlap it on the other axis.
+pub fn find_neighbour(
+    windows: &[(usize, Rect)],
+    from: Rect,
+    direction: FocusDirection,
+) -> Option<usize> {
+    windows
+        .iter()
+        .filter_map(|(window, area)| {
+            let (distance, overlaps) = match direction {
+                FocusDirection::Left if area.right() <= from.x => (
+                    from.x - area.right(),
+                    ranges_overlap(area.y, area.bottom(), from.y, from.bottom()),
+                ),
+                FocusDirection::Right if area.x >= from.right() => (
+                    area.x - from.right(),
+                    ranges_overlap(area.y, area.bottom(), from.y, from.bottom()),
+                ),
+                FocusDirection::Up if area.bottom() <= from.y => (
+                    from.y - area.bottom(),
+                    ranges_overlap(area.x, area.right(), from.x, from.right()),
+                ),
+                FocusDirection::Down if area.y >= from.bottom() => (
+                    area.y - from.bottom(),
+                    ranges_overlap(area.x, area.right(), from.x, from.right()),
+                ),
+                _ => return None,
+            };
+            Some((!overlaps, distance, *window))
+        })
+        .min()
+        .map(|(_, _, window)| window)
+}
+
+fn split_length(length: u16, ratio: u16) -> u16 {
+    if length < 2 {
+        return length;
+    }
+    let first = (u32::from(length) * u32::from(ratio) / 100) as u16;
+    first.clamp(1, length - 1)
+}
+
+fn ranges_overlap(start: u16, end: u16, other_start: u16, other_end: u16) -> bool {
+    start < other_end && other_start < end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 81,
+        height: 20,
+    };
+
+    fn arrange(layout: &Layout) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+        let mut windows = vec![];
+        let mut separators = vec![];
+        layout.arrange(AREA, &mut windows, &mut separators);
+        (windows, separators)
+    }
+
+    #[test]
+    fn vertical_split_leaves_room_for_separator() {
+        let mut layout = Layout::Window(0);
+        layout.split(0, 1, SplitDirection::Vertical);
+        let (windows, separators) = arrange(&layout);
+
+        assert_eq!(windows[0].1, Rect { width: 40, ..AREA });
+        assert_eq!(
+            windows[1].1,
+            Rect {
+                x: 41,
+                width: 40,
+                ..AREA
+            }
+        );
+        assert_eq!(
+            separators,
+            vec![Rect {
+                x: 40,
+                width: 1,
+                ..AREA
+            }]
+        );
+    }
+
+    #[test]
+    fn resize_and_remove_nested_windows() {
+        let mut layout = Layout::Window(0);
+        layout.split(0, 1, SplitDirection::Vertical);
+        layout.split(1, 2, SplitDirection::Horizontal);
+
+        assert!(layout.resize(2, 10));
+        let (windows, _) = arrange(&layout);
+        assert_eq!(windows[1].1.height, 8);
+        assert_eq!(windows[2].1.height, 12);
+
+        assert!(layout.remove(1));
+        assert!(!layout.contains(1));
+        let (windows, _) = arrange(&layout);
+        assert_eq!(
+            windows[1],
+            (
+                2,
+                Rect {
+                    x: 41,
+                    width: 40,
+                    ..AREA
+                }
+            )
+        );
+        assert!(!Layout::Window(0).remove(0));
+    }
+
+    #[test]
+    fn finds_neighbour_in_direction() {
+        let mut layout = Layout::Window(0);
+        layout.split(0, 1, SplitDirection::Vertical);
+        layout.split(1, 2, SplitDirection::Horizontal);
+        let (windows, _) = arrange(&layout);
+
+        let right_bottom = windows[2].1;
+        assert_eq!(
+            find_neighbour(&windows, right_bottom, FocusDirection::Up),
+            Some(1)
+        );
+        assert_eq!(
+            find_neighbour(&windows, right_bottom, FocusDirection::Left),
+            Some(0)
+        );
+        assert_eq!(
+            find_neighbour(&windows, right_bottom, FocusDirection::Right),
+            None
+        );
+    }
+}
@@ -1,26 +1,126 @@
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::module_name_repetitions, clippy::cast_possible_truncation)]
 
+mod buffer;
+mod completion;
+mod config;
+mod diff;
 mod document;
 mod editor;
+mod error;
+mod highlighting;
+mod layout;
+mod prompt;
+mod status_line;
 mod terminal;
+mod theme;
 
-use clap::{Arg, Command};
-use log::{debug, LevelFilter};
+use clap::error::ErrorKind;
+use clap::{value_parser, Arg, ArgAction, Command};
+use error::Error;
+use log::{debug, error, LevelFilter};
 use log4rs::append::file::FileAppender;
 use log4rs::config::{Appender, Config, Root};
 use log4rs::encode::pattern::PatternEncoder;
+use std::env;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process;
 use std::str::FromStr;
+use terminal::ColorSupport;
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const EDIT_FILE_PATH_ARG: &str = "edit_file_path";
 const LOG_FILE_PATH_ARG: &str = "log_file_path";
 const LOG_LEVEL_ARG: &str = "log_level";
+const LINE_ARG: &str = "line";
+const COLUMN_ARG: &str = "column";
+const COLOR_ARG: &str = "color";
+const THEME_ARG: &str = "theme";
+const READ_ONLY_ARG: &str = "readonly";
+/// Started under this name, as through a link, the editor opens files
+/// read-only like `--readonly` does.
+const VIEW_PROGRAM_NAME: &str = concat!(env!("CARGO_PKG_NAME"), "-view");
 const LOG_FILE_APPENDER_KEY: &str = "log_file";
+/// Exit status base when a signal ends the editor, following the shell
+/// convention of `128 + signal number`.
+const TERMINATED_EXIT_CODE_BASE: i32 = 128;
 
-fn main() {
-    let matches = Command::new(APP_NAME)
+/// A file argument with an optional 1-based cursor position, as accepted in
+/// the `file:line[:column]` form.
+#[derive(Debug, PartialEq, Eq)]
+struct FileLocation {
+    path: String,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+impl FileLocation {
+    /// Takes up to two trailing numbers off `arg` as the line and column,
+    /// ignoring a single trailing colon as left by compiler messages. An
+    /// existing file is taken as it is, even with colons in its name.
+    fn parse(arg: &str) -> Self {
+        let mut path = arg;
+        let mut numbers: Vec<usize> = vec![];
+        if !Path::new(arg).exists() {
+            path = path
+                .strip_suffix(':')
+                .filter(|rest| !rest.is_empty())
+                .unwrap_or(path);
+            while numbers.len() < 2 {
+                let Some((rest, number)) = path.rsplit_once(':') else {
+                    break;
+                };
+                match number.parse() {
+                    Ok(number) if !rest.is_empty() => {
+                        numbers.insert(0, number);
+                        path = rest;
+                    },
+                    _ => break,
+                }
+            }
+        }
+
+        FileLocation {
+            path: String::from(path),
+            line: numbers.first().copied(),
+            column: numbers.get(1).copied(),
+        }
+    }
+}
+
+/// Turns the file arguments into locations. A leading `+LINE` applies to the
+/// first file, as do `line` and `column` from `--line` and `--column`, with
+/// `+LINE` winning over `--line` and both over a line in the file argument.
+fn parse_file_args(
+    args: &[&str],
+    line: Option<usize>,
+    column: Option<usize>,
+) -> Result<Vec<FileLocation>, &'static str> {
+    let mut args = args;
+    let mut line = line;
+    if let Some(jump_line) = args
+        .first()
+        .and_then(|arg| arg.strip_prefix('+'))
+        .and_then(|jump_line| jump_line.parse::<usize>().ok())
+    {
+        args = &args[1..];
+        line = Some(jump_line);
+    }
+    if args.is_empty() {
+        return Err("expected a file path after `+LINE`");
+    }
+
+    let mut file_locations: Vec<FileLocation> =
+        args.iter().map(|arg| FileLocation::parse(arg)).collect();
+    file_locations[0].line = line.or(file_locations[0].line);
+    file_locations[0].column = column.or(file_locations[0].column);
+    Ok(file_locations)
+}
+
+fn command() -> Command {
+    Command::new(APP_NAME)
         .version(VERSION)
         .arg(
             Arg::new(LOG_FILE_PATH_ARG)
@@ -36,32 +136,229 @@ fn main() {
                 .short('l')
                 .default_value("info"),
         )
-        .arg(Arg::new(EDIT_FILE_PATH_ARG).required(true).index(1))
-        .get_matches();
+        .arg(
+            Arg::new(LINE_ARG)
+                .required(false)
+                .long("line")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new(COLUMN_ARG)
+                .required(false)
+                .long("column")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new(COLOR_ARG)
+                .required(false)
+                .long("color")
+                .value_parser(["auto", "truecolor", "256", "16", "mono"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new(THEME_ARG)
+                .required(false)
+                .long("theme")
+                .default_value(theme::DEFAULT_THEME),
+        )
+        .arg(
+            Arg::new(READ_ONLY_ARG)
+                .required(false)
+                .long("readonly")
+                .short('R')
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new(EDIT_FILE_PATH_ARG)
+                .required(true)
+                .index(1)
+                .num_args(1..)
+                .value_name("[+LINE] FILE[:LINE[:COLUMN]]"),
+        )
+}
+
+fn main() {
+    let mut command = command();
+    let matches = command.get_matches_mut();
+
+    let log_file_path = matches
+        .get_one::<String>(LOG_FILE_PATH_ARG)
+        .expect("log file path has a default value");
+    let log_level = matches
+        .get_one::<String>(LOG_LEVEL_ARG)
+        .expect("log level has a default value");
+    if let Err(err) = init_logger(log_file_path, log_level) {
+        exit_with_error(&err);
+    }
+
+    let color_support = match matches
+        .get_one::<String>(COLOR_ARG)
+        .expect("color has a default value")
+        .as_str()
+    {
+        "truecolor" => ColorSupport::TrueColor,
+        "256" => ColorSupport::Ansi256,
+        "16" => ColorSupport::Ansi16,
+        "mono" => ColorSupport::Monochrome,
+        _ => ColorSupport::detect(),
+    };
+
+    let theme_name = matches
+        .get_one::<String>(THEME_ARG)
+        .expect("theme has a default value");
+
+    let read_only = matches.get_flag(READ_ONLY_ARG) || invoked_as_viewer();
+
+    let file_args: Vec<&str> = matches
+        .get_many::<String>(EDIT_FILE_PATH_ARG)
+        .expect("file path is a required argument")
+        .map(String::as_str)
+        .collect();
+    let file_locations = match parse_file_args(
+        &file_args,
+        matches.get_one::<usize>(LINE_ARG).copied(),
+        matches.get_one::<usize>(COLUMN_ARG).copied(),
+    ) {
+        Ok(file_locations) => file_locations,
+        Err(message) => command
+            .error(ErrorKind::MissingRequiredArgument, message)
+            .exit(),
+    };
+
+    match run(&file_locations, color_support, theme_name, read_only) {
+        Ok(0) => (),
+        Ok(exit_code) => process::exit(exit_code),
+        Err(err) => exit_with_error(&err),
+    }
+}
 
-    let log_file_path = matches.get_one::<String>(LOG_FILE_PATH_ARG).unwrap();
-    let log_level = matches.get_one::<String>(LOG_LEVEL_ARG).unwrap();
+/// Runs the editor on the given files and returns the exit code. The editor
+/// is dropped before returning, so the terminal is restored by then.
+fn run(
+    file_locations: &[FileLocation],
+    color_support: ColorSupport,
+    theme_name: &str,
+    read_only: bool,
+) -> error::Result<i32> {
+    let config = config::Config::load()?;
+    let theme = theme::Theme::load(theme_name)?;
+    let mut documents = file_locations
+        .iter()
+        .map(|file_location| document::Document::new(&file_location.path))
+        .collect::<error::Result<Vec<_>>>()?;
+    if read_only {
+        for document in &mut documents {
+            document.set_read_only(true);
+        }
+    }
+    let terminal = terminal::Terminal::new(color_support)?;
+    let mut editor = editor::Editor::new(terminal, documents, theme, config);
+    for (buffer_index, file_location) in file_locations.iter().enumerate() {
+        if file_location.line.is_some() || file_location.column.is_some() {
+            editor.goto_location(
+                buffer_index,
+                file_location.line.unwrap_or(1),
+                file_location.column.unwrap_or(1),
+            );
+        }
+        debug!("RTE open {} file", file_location.path);
+    }
+
+    editor.run()?;
+    Ok(editor
+        .terminated_by()
+        .map_or(0, |signal| TERMINATED_EXIT_CODE_BASE + signal))
+}
+
+fn invoked_as_viewer() -> bool {
+    env::args_os().next().is_some_and(|program| {
+        Path::new(&program).file_name() == Some(OsStr::new(VIEW_PROGRAM_NAME))
+    })
+}
+
+fn init_logger(log_file_path: &str, log_level: &str) -> error::Result<()> {
+    let level_filter = LevelFilter::from_str(log_level)
+        .map_err(|_| Error::Config(format!("unknown log level `{log_level}`")))?;
     let file_appender = FileAppender::builder()
         .encoder(Box::new(PatternEncoder::new("{d} | {l} - {m}{n}")))
         .build(log_file_path)
-        .unwrap();
+        .map_err(|err| Error::Config(format!("can't open log file {log_file_path}: {err}")))?;
 
     let config = Config::builder()
         .appender(Appender::builder().build(LOG_FILE_APPENDER_KEY, Box::new(file_appender)))
         .build(
             Root::builder()
                 .appender(LOG_FILE_APPENDER_KEY)
-                .build(LevelFilter::from_str(log_level).unwrap()),
+                .build(level_filter),
         )
-        .unwrap();
+        .map_err(|err| Error::Config(err.to_string()))?;
+
+    log4rs::init_config(config).map_err(|err| Error::Config(err.to_string()))?;
+    Ok(())
+}
+
+fn exit_with_error(err: &Error) -> ! {
+    error!("{err}");
+    eprintln!("{APP_NAME}: {err}");
+    process::exit(err.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(path: &str, line: Option<usize>, column: Option<usize>) -> FileLocation {
+        FileLocation {
+            path: String::from(path),
+            line,
+            column,
+        }
+    }
 
-    log4rs::init_config(config).unwrap();
+    #[test]
+    fn parses_file_positions() {
+        let parse = |arg| FileLocation::parse(arg);
+        assert_eq!(parse("a.rs:12:5"), location("a.rs", Some(12), Some(5)));
+        assert_eq!(parse("a.rs:12"), location("a.rs", Some(12), None));
+        assert_eq!(parse("a.rs:12:5:"), location("a.rs", Some(12), Some(5)));
+        assert_eq!(parse("a.rs:"), location("a.rs", None, None));
+        assert_eq!(parse("a:b:3"), location("a:b", Some(3), None));
+        assert_eq!(parse(":5"), location(":5", None, None));
+        assert_eq!(parse(":"), location(":", None, None));
+        assert_eq!(parse("a.rs:1:2:3"), location("a.rs:1", Some(2), Some(3)));
+        assert_eq!(parse("a.rs:x:3"), location("a.rs:x", Some(3), None));
 
-    let edit_file_path = matches.get_one::<String>(EDIT_FILE_PATH_ARG).unwrap();
-    let document = document::Document::new(edit_file_path).unwrap();
-    let terminal = terminal::Terminal::new().unwrap();
-    let mut editor = editor::Editor::new(terminal, document);
+        let existing = std::env::temp_dir().join(format!("rte-args-{}:7", process::id()));
+        std::fs::write(&existing, "").unwrap();
+        let existing_path = existing.to_string_lossy();
+        assert_eq!(parse(&existing_path), location(&existing_path, None, None));
+        std::fs::remove_file(&existing).unwrap();
+    }
 
-    debug!("RTE open {} file", edit_file_path);
-    editor.run().unwrap();
+    #[test]
+    fn applies_jump_line_and_options_to_first_file() {
+        assert_eq!(
+            parse_file_args(&["+3", "a.rs:9:4", "b.rs:2"], None, None),
+            Ok(vec![
+                location("a.rs", Some(3), Some(4)),
+                location("b.rs", Some(2), None),
+            ])
+        );
+        assert_eq!(
+            parse_file_args(&["+3", "a.rs"], Some(8), Some(6)),
+            Ok(vec![location("a.rs", Some(3), Some(6))])
+        );
+        assert_eq!(
+            parse_file_args(&["a.rs:9"], Some(8), None),
+            Ok(vec![location("a.rs", Some(8), None)])
+        );
+        assert_eq!(
+            parse_file_args(&["+x"], None, None),
+            Ok(vec![location("+x", None, None)])
+        );
+        assert_eq!(
+            parse_file_args(&["+3"], None, None),
+            Err("expected a file path after `+LINE`")
+        );
+    }
 }
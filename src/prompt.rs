@@ -0,0 +1,159 @@
+use crate::completion;
+
+const CANDIDATES_SEPARATOR: &str = "  ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptKind {
+    GoTo,
+    OpenFile,
+    CloseBuffer,
+    ForceSave,
+    SwitchTheme,
+    /// Asks what to do about the file of the active buffer changing on disk.
+    /// `force` is set when saving a read-only buffer was confirmed already.
+    FileChanged {
+        force: bool,
+    },
+    SaveAs,
+    WriteCopy,
+    InsertFile,
+    /// Asks whether to create the missing directory of `file_path` before
+    /// writing to it, as a copy or not.
+    CreateDirectory {
+        file_path: String,
+        copy: bool,
+    },
+}
+
+impl PromptKind {
+    /// Whether the prompt asks for a file path, which Tab completes.
+    pub fn asks_for_path(&self) -> bool {
+        matches!(
+            self,
+            PromptKind::OpenFile
+                | PromptKind::SaveAs
+                | PromptKind::WriteCopy
+                | PromptKind::InsertFile
+        )
+    }
+}
+
+/// The matches offered for the input, shown after it until the input is
+/// edited.
+struct Completion {
+    candidates: Vec<String>,
+    /// The candidate currently in the input, once Tab cycles through them.
+    selected: Option<usize>,
+    /// Where the last path component starts in the candidates.
+    name_start: usize,
+}
+
+pub struct Prompt {
+    pub kind: PromptKind,
+    label: String,
+    input: String,
+    completion: Option<Completion>,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, label: impl Into<String>) -> Self {
+        Prompt {
+            kind,
+            label: label.into(),
+            input: String::new(),
+            completion: None,
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        self.input.push(c);
+        self.completion = None;
+    }
+
+    pub fn remove_char(&mut self) {
+        self.input.pop();
+        self.completion = None;
+    }
+
+    /// Completes the input from the `candidates` it lists. A single one
+    /// replaces the input, several extend it to their common prefix and are
+    /// shown, and every further call puts the next of them into the input.
+    pub fn complete(&mut self, candidates: impl FnOnce(&str) -> Vec<String>) {
+        if let Some(completion) = &mut self.completion {
+            let selected = completion
+                .selected
+                .map_or(0, |selected| (selected + 1) % completion.candidates.len());
+            completion.selected = Some(selected);
+            self.input.clone_from(&completion.candidates[selected]);
+            return;
+        }
+
+        let mut candidates = candidates(&self.input);
+        match candidates.len() {
+            0 => (),
+            1 => self.input = candidates.remove(0),
+            _ => {
+                self.input = completion::common_prefix(&candidates);
+                self.completion = Some(Completion {
+                    name_start: self.input.rfind('/').map_or(0, |index| index + 1),
+                    candidates,
+                    selected: None,
+                });
+            },
+        }
+    }
+
+    /// The label and input, followed by the completion candidates with the
+    /// selected one in brackets.
+    pub fn text(&self) -> String {
+        let mut text = format!("{}{}", self.label, self.input);
+        if let Some(completion) = &self.completion {
+            for (index, candidate) in completion.candidates.iter().enumerate() {
+                let name = &candidate[completion.name_start..];
+                text.push_str(CANDIDATES_SEPARATOR);
+                if completion.selected == Some(index) {
+                    text.push('[');
+                    text.push_str(name);
+                    text.push(']');
+                } else {
+                    text.push_str(name);
+                }
+            }
+        }
+        text
+    }
+
+    pub fn cursor_x(&self) -> usize {
+        self.label.chars().count() + self.input.chars().count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completion_cycles_through_candidates() {
+        let mut prompt = Prompt::new(PromptKind::OpenFile, "Open: ");
+        prompt.insert_char('s');
+        let candidates = |_: &str| vec![String::from("src/"), String::from("sort.rs")];
+
+        prompt.complete(candidates);
+        assert_eq!(prompt.input(), "s");
+        assert_eq!(prompt.text(), "Open: s  src/  sort.rs");
+        prompt.complete(candidates);
+        assert_eq!(prompt.input(), "src/");
+        prompt.complete(candidates);
+        prompt.complete(candidates);
+        assert_eq!(prompt.text(), "Open: src/  [src/]  sort.rs");
+
+        prompt.insert_char('m');
+        assert_eq!(prompt.text(), "Open: src/m");
+        prompt.complete(|_| vec![String::from("src/main.rs")]);
+        assert_eq!(prompt.input(), "src/main.rs");
+    }
+}
@@ -0,0 +1,331 @@
+use crate::buffer::Position;
+use crate::document::Document;
+use std::env;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Documents are always read and written as UTF-8.
+const ENCODING: &str = "utf-8";
+const MODIFIED_FLAG: &str = "[+]";
+const READ_ONLY_FLAG: &str = "[RO]";
+const OVERWRITE_MODE: &str = "OVR";
+const TRUNCATION_MARK: char = '\u{2026}';
+const SEGMENT_SEPARATOR: &str = " ";
+const SECONDS_PER_MINUTE: u64 = 60;
+
+const SEGMENT_NAMES: [(&str, Segment); 12] = [
+    ("name", Segment::FileName),
+    ("path", Segment::Path),
+    ("flags", Segment::Flags),
+    ("position", Segment::Position),
+    ("percentage", Segment::Percentage),
+    ("filetype", Segment::FileType),
+    ("encoding", Segment::Encoding),
+    ("line_ending", Segment::LineEnding),
+    ("selection", Segment::Selection),
+    ("buffers", Segment::Buffers),
+    ("clock", Segment::Clock),
+    ("mode", Segment::Mode),
+];
+
+/// A piece of information shown in the status line of a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    FileName,
+    /// The file path relative to the working directory.
+    Path,
+    Flags,
+    /// The 1-based `line:column` of the cursor, the column counting
+    /// characters.
+    Position,
+    Percentage,
+    FileType,
+    Encoding,
+    LineEnding,
+    /// The number of selected characters.
+    Selection,
+    /// The buffer number and the number of buffers.
+    Buffers,
+    Clock,
+    /// `OVR` while typing replaces text.
+    Mode,
+}
+
+/// What the status line of a window shows about its buffer.
+pub struct StatusInfo<'a> {
+    pub document: &'a Document,
+    pub cursor_position: Position,
+    pub selection: Option<(Position, Position)>,
+    pub buffer_index: usize,
+    pub buffers_count: usize,
+    pub overwrite: bool,
+}
+
+/// The segments shown on the left and right side of the status line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusLine {
+    pub left: Vec<Segment>,
+    pub right: Vec<Segment>,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        StatusLine {
+            left: vec![Segment::Flags, Segment::Path],
+            right: vec![
+                Segment::Mode,
+                Segment::Selection,
+                Segment::FileType,
+                Segment::Position,
+                Segment::Percentage,
+                Segment::Buffers,
+            ],
+        }
+    }
+}
+
+impl StatusLine {
+    /// Whether the status line has to be redrawn every minute.
+    pub fn shows_clock(&self) -> bool {
+        self.left.contains(&Segment::Clock) || self.right.contains(&Segment::Clock)
+    }
+
+    /// Renders the segments into a line of exactly `width` columns. When
+    /// both sides do not fit, the right one is cut to half of the width
+    /// first and the left one takes the rest.
+    pub fn render(&self, info: &StatusInfo, width: usize) -> String {
+        let left = render_segments(&self.left, info);
+        let right = render_segments(&self.right, info);
+        let separator = usize::from(!left.is_empty() && !right.is_empty());
+
+        let (left_room, right_room) = if left.width() + separator + right.width() <= width {
+            (left.width(), right.width())
+        } else {
+            let right_room = right.width().min(width / 2);
+            (width.saturating_sub(right_room + separator), right_room)
+        };
+        let left = truncate(&left, left_room);
+        let right = truncate(&right, right_room);
+        let gap = width.saturating_sub(left.width() + right.width());
+        format!("{left}{}{right}", " ".repeat(gap))
+    }
+}
+
+/// Parses a space separated list of segment names.
+pub fn parse_segments(value: &str) -> Result<Vec<Segment>, String> {
+    value
+        .split_whitespace()
+        .map(|name| {
+            SEGMENT_NAMES
+                .iter()
+                .find(|(segment_name, _)| *segment_name == name)
+                .map(|(_, segment)| *segment)
+                .ok_or_else(|| format!("unknown status line segment `{name}`"))
+        })
+        .collect()
+}
+
+/// The time until the clock shows the next minute.
+pub fn until_next_minute() -> Duration {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    Duration::from_secs(SECONDS_PER_MINUTE - seconds % SECONDS_PER_MINUTE)
+}
+
+impl Segment {
+    /// Returns the text of the segment, or `None` when there is nothing to
+    /// show, such as the flags of an unmodified, writable document.
+    fn render(self, info: &StatusInfo) -> Option<String> {
+        let document = info.document;
+        let text = match self {
+            Segment::FileName => document.file_name(),
+            Segment::Path => relative_path(&document.file_path),
+            Segment::Flags => {
+                let read_only = document.is_read_only().then_some(READ_ONLY_FLAG);
+                let modified = document.is_modified().then_some(MODIFIED_FLAG);
+                if read_only.is_none() && modified.is_none() {
+                    return None;
+                }
+                format!(
+                    "{}{}",
+                    read_only.unwrap_or_default(),
+                    modified.unwrap_or_default()
+                )
+            },
+            Segment::Position => {
+                let Position { x, y } = info.cursor_position;
+                format!("{}:{}", y + 1, document.char_column(y, x) + 1)
+            },
+            Segment::Percentage => {
+                let percentage = (info.cursor_position.y + 1) * 100 / document.len().max(1);
+                format!("{percentage}%")
+            },
+            Segment::FileType => String::from(document.file_type()?),
+            Segment::Encoding => String::from(ENCODING),
+            Segment::LineEnding => String::from(document.line_ending().name()),
+            Segment::Selection => {
+                let (start, end) = info.selection?;
+                format!("{} selected", selection_size(document, start, end))
+            },
+            Segment::Buffers => format!("[{}/{}]", info.buffer_index + 1, info.buffers_count),
+            Segment::Clock => clock()?,
+            Segment::Mode => {
+                if !info.overwrite {
+                    return None;
+                }
+                String::from(OVERWRITE_MODE)
+            },
+        };
+        Some(text)
+    }
+}
+
+fn render_segments(segments: &[Segment], info: &StatusInfo) -> String {
+    segments
+        .iter()
+        .filter_map(|segment| segment.render(info))
+        .collect::<Vec<_>>()
+        .join(SEGMENT_SEPARATOR)
+}
+
+/// Cuts `text` to at most `width` columns, marking the cut with an ellipsis.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return String::from(text);
+    }
+    let mut truncated = String::new();
+    let mut truncated_width = 0;
+    let room = width.saturating_sub(TRUNCATION_MARK.width().unwrap_or(1));
+    for symbol in text.chars() {
+        let symbol_width = symbol.width().unwrap_or(1);
+        if truncated_width + symbol_width > room {
+            break;
+        }
+        truncated.push(symbol);
+        truncated_width += symbol_width;
+    }
+    if width > 0 {
+        truncated.push(TRUNCATION_MARK);
+    }
+    truncated
+}
+
+fn relative_path(file_path: &str) -> String {
+    env::current_dir()
+        .ok()
+        .and_then(|dir| {
+            let relative = Path::new(file_path).strip_prefix(dir).ok()?;
+            Some(relative.display().to_string())
+        })
+        .unwrap_or_else(|| String::from(file_path))
+}
+
+/// Counts the characters between `start` and `end`, with a line break
+/// counting as one.
+fn selection_size(document: &Document, start: Position, end: Position) -> usize {
+    let start_column = document.char_column(start.y, start.x);
+    let end_column = document.char_column(end.y, end.x);
+    if start.y == end.y {
+        return end_column.saturating_sub(start_column);
+    }
+    let first = document.get_row(start.y).chars().count() + 1 - start_column;
+    let middle: usize = (start.y + 1..end.y)
+        .map(|row| document.get_row(row).chars().count() + 1)
+        .sum();
+    first + middle + end_column
+}
+
+/// The local time as `HH:MM`.
+fn clock() -> Option<String> {
+    // SAFETY: `time` accepts a null pointer and `localtime_r` only writes to
+    // the given `tm`, which is plain data that may start zeroed.
+    let time = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut time: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&raw const now, &raw mut time).is_null() {
+            return None;
+        }
+        time
+    };
+    Some(format!("{:02}:{:02}", time.tm_hour, time.tm_min))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(document: &Document, cursor_position: Position) -> StatusInfo<'_> {
+        StatusInfo {
+            document,
+            cursor_position,
+            selection: None,
+            buffer_index: 0,
+            buffers_count: 2,
+            overwrite: false,
+        }
+    }
+
+    #[test]
+    fn renders_segments_on_both_sides() {
+        let document = Document::from_text("src/main.rs", "first\nsecond\nthird\nfourth");
+        let status_line = StatusLine {
+            left: parse_segments("flags name encoding").unwrap(),
+            right: parse_segments("line_ending position percentage buffers").unwrap(),
+        };
+        let info = info(&document, Position { x: 2, y: 1 });
+
+        assert_eq!(
+            status_line.render(&info, 40),
+            "main.rs utf-8           LF 2:3 50% [1/2]"
+        );
+    }
+
+    #[test]
+    fn truncates_by_display_width() {
+        let document = Document::from_text("日本語のファイル.txt", "");
+        let status_line = StatusLine {
+            left: vec![Segment::Path],
+            right: vec![Segment::Position, Segment::FileType],
+        };
+        let info = info(&document, Position::default());
+
+        let line = status_line.render(&info, 20);
+        assert_eq!(line, "日本語のフ…  1:1 txt");
+        assert_eq!(line.width(), 20);
+        assert_eq!(status_line.render(&info, 8), "日… 1:1…");
+    }
+
+    #[test]
+    fn counts_selected_characters_across_lines() {
+        let document = Document::from_text("test.txt", "abc\nde\nfgh");
+        let start = Position { x: 1, y: 0 };
+        assert_eq!(selection_size(&document, start, Position { x: 3, y: 0 }), 2);
+        assert_eq!(selection_size(&document, start, Position { x: 2, y: 2 }), 8);
+    }
+
+    #[test]
+    fn counts_multibyte_characters_once() {
+        let document = Document::from_text("test.txt", "\u{e9}t\u{e9}\nd\u{e9}j\u{e0}");
+        // Byte positions after the first "é" and after "dé".
+        let start = Position { x: 2, y: 0 };
+        let end = Position { x: 3, y: 1 };
+        assert_eq!(selection_size(&document, start, Position { x: 5, y: 0 }), 2);
+        assert_eq!(selection_size(&document, start, end), 5);
+
+        let status_line = StatusLine {
+            left: vec![],
+            right: vec![Segment::Position],
+        };
+        assert_eq!(status_line.render(&info(&document, end), 3), "2:3");
+    }
+
+    #[test]
+    fn rejects_unknown_segments() {
+        assert_eq!(
+            parse_segments("name size"),
+            Err(String::from("unknown status line segment `size`"))
+        );
+    }
+}
@@ -1,26 +1,90 @@
-use crossbeam::channel::{select, unbounded, Receiver, RecvError};
-#[cfg(not(feature = "extended-siginfo"))]
-use signal_hook::consts::signal::SIGWINCH;
-use signal_hook::iterator::{Handle, Signals};
-use std::error;
-use std::io::{self, Write};
-use std::thread::{self, JoinHandle};
+mod color;
+#[cfg(feature = "crossterm-backend")]
+mod crossterm_backend;
+mod events;
+mod frame;
+mod keymap;
+#[cfg(all(feature = "termion-backend", not(feature = "crossterm-backend")))]
+mod termion_backend;
+#[cfg(test)]
+pub mod test_backend;
+
+use crate::error::{self, Error};
+use crossbeam::channel::RecvError;
+use frame::Frame;
+use std::io;
+use std::iter;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
-use termion::color;
-use termion::event::{Event, Key};
-use termion::input::TermRead;
-use termion::raw::{IntoRawMode, RawTerminal};
-use termion::screen::AlternateScreen;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+pub use color::{Color, ColorSupport};
+#[cfg(feature = "crossterm-backend")]
+pub use crossterm_backend::CrosstermBackend;
+pub use frame::{Attributes, Rgb, Style};
+#[cfg(all(feature = "termion-backend", not(feature = "crossterm-backend")))]
+pub use termion_backend::TermionBackend;
+
+#[cfg(not(any(feature = "termion-backend", feature = "crossterm-backend")))]
+compile_error!("either the `termion-backend` or the `crossterm-backend` feature must be enabled");
 
 const PADDING_BUTTON: u16 = 2;
-const EXIT_CHARACTER: char = 'q';
-const SAVE_CHARACTER: char = 's';
+/// Saves the window and icon titles on the terminal's title stack (XTWINOPS).
+const SAVE_TITLE_SEQUENCE: &str = "\x1b[22;0t";
+const RESTORE_TITLE_SEQUENCE: &str = "\x1b[23;0t";
+
+/// The drawing, cursor, size and input primitives `Terminal` is built on.
+pub trait Backend {
+    /// Returns the full screen size as `(width, height)`.
+    fn size(&self) -> Result<(u16, u16), io::Error>;
+    fn cursor_hide(&mut self) -> Result<(), io::Error>;
+    fn cursor_show(&mut self) -> Result<(), io::Error>;
+    /// Moves the cursor to the 0-based `x` and `y` cell.
+    fn cursor_to_position(&mut self, x: u16, y: u16) -> Result<(), io::Error>;
+    /// Replaces the colors and attributes of the following text. `None`
+    /// colors are the terminal defaults.
+    fn set_style(
+        &mut self,
+        background: Option<Color>,
+        foreground: Option<Color>,
+        attributes: Attributes,
+    ) -> Result<(), io::Error>;
+    /// Prints `text` at the cursor position, moving the cursor past it.
+    fn print(&mut self, text: &str) -> Result<(), io::Error>;
+    fn flush(&mut self) -> Result<(), io::Error>;
+    /// Hands the terminal back to the shell (main screen, cooked mode) and
+    /// stops the process with `SIGTSTP`.
+    fn suspend(&mut self) -> Result<(), io::Error>;
+    /// Takes the terminal over again after the process was continued.
+    fn resume(&mut self) -> Result<(), io::Error>;
+    fn pull_event(&mut self) -> Result<TerminalEvent, RecvError>;
+    /// Returns an already received event without blocking.
+    fn try_pull_event(&mut self) -> Option<TerminalEvent>;
+    /// Delivers `TerminalEvent::Timer(timer)` once `delay` has passed.
+    fn schedule_timer(&mut self, timer: Timer, delay: Duration);
+    /// Sets the window title, see `title_sequence`.
+    fn set_title(&mut self, title: &str) -> Result<(), io::Error>;
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), io::Error>;
+}
 
+/// Drawing calls only update the next frame; `flush` sends the cells that
+/// changed since the previously flushed frame to the backend.
 pub struct Terminal {
-    stdout: AlternateScreen<RawTerminal<io::Stdout>>,
+    backend: Box<dyn Backend>,
     size: ScreenSize,
-    input_event_handler: InputEventHandler,
-    syscall_signal_handler: SyscallHandler,
+    frame: Frame,
+    previous_frame: Option<Frame>,
+    cursor: (u16, u16),
+    style: Style,
+    color_support: ColorSupport,
+    cursor_visible: bool,
+    flushed_cursor: Option<((u16, u16), bool)>,
+    title: Option<String>,
+    flushed_title: Option<String>,
+    cursor_shape: CursorShape,
+    flushed_cursor_shape: CursorShape,
 }
 
 pub struct ScreenSize {
@@ -28,27 +92,112 @@ pub struct ScreenSize {
     height: u16,
 }
 
+/// A rectangular region of the screen, in 0-based cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn right(self) -> u16 {
+        self.x.saturating_add(self.width)
+    }
+
+    pub fn bottom(self) -> u16 {
+        self.y.saturating_add(self.height)
+    }
+}
+
+/// A cursor shape as set with DECSCUSR.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    /// The shape the user configured for the terminal.
+    #[default]
+    Default,
+    Block {
+        blinking: bool,
+    },
+    Underline {
+        blinking: bool,
+    },
+    Bar {
+        blinking: bool,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub enum TerminalEvent {
     Input(InputEvent),
     Syscall(SyscallEvent),
-    Empty,
+    Timer(Timer),
+}
+
+/// A timer scheduled with `Terminal::schedule_timer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timer {
+    /// The status message with the given serial number has been shown long
+    /// enough.
+    MessageExpiry(u64),
+    /// The minute shown by the status line clock is over.
+    ClockTick,
+    /// Time to look for files changed on disk.
+    DiskCheck,
 }
 
 #[derive(Debug, Clone)]
 pub enum SyscallEvent {
     WindowSizeChanged,
-    Unsupported,
+    Continued,
+    /// The process was asked to terminate by the given signal.
+    Terminated(i32),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub enum InputEvent {
     Key(KeyEvent),
-    Empty,
+    Mouse(MouseEvent),
     Unsupported,
 }
 
-#[derive(Debug, Clone)]
+/// A mouse action at the 0-based `x` and `y` screen cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    pub x: u16,
+    pub y: u16,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEventKind {
+    Press(MouseButton),
+    /// The pointer moved while the left button is held.
+    Drag,
+    Release,
+    ScrollUp,
+    ScrollDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+/// Modifier keys held during a mouse event. termion does not report them,
+/// so they are only set with the crossterm backend.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub control: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyEvent {
     Char(char),
     Up,
@@ -58,66 +207,172 @@ pub enum KeyEvent {
     Backspace,
     Exit,
     SaveDocument,
+    SaveAs,
+    WriteCopy,
+    InsertFile,
+    JumpToMatchingBracket,
+    GoTo,
+    OpenFile,
+    NextBuffer,
+    PreviousBuffer,
+    ListBuffers,
+    CloseBuffer,
+    SplitHorizontal,
+    SplitVertical,
+    CloseWindow,
+    GrowWindow,
+    ShrinkWindow,
+    FocusLeftWindow,
+    FocusRightWindow,
+    FocusUpperWindow,
+    FocusLowerWindow,
+    Suspend,
+    SwitchTheme,
+    ToggleOverwrite,
+    Help,
+    Escape,
     Unsupported,
 }
 
-impl Drop for Terminal {
-    fn drop(&mut self) {
-        self.syscall_signal_handler.signals_handle.close();
-        self.flush().unwrap();
-
-        let input_handler_join_result = self
-            .input_event_handler
-            .join_handle
-            .take()
-            .expect("join handler is not found")
-            .join()
-            .expect("join thread operation is failed");
-
-        if let Err(err) = input_handler_join_result {
-            log::error!("{}", err);
-        }
-
-        let syscall_handler_join_result = self
-            .syscall_signal_handler
-            .join_handle
-            .take()
-            .expect("join handler is not found")
-            .join()
-            .expect("join thread operation is failed");
+impl Terminal {
+    #[cfg(all(feature = "termion-backend", not(feature = "crossterm-backend")))]
+    pub fn new(color_support: ColorSupport) -> error::Result<Self> {
+        let backend = TermionBackend::new().map_err(Error::Terminal)?;
+        install_panic_hook(backend.panic_restorer());
+        Terminal::with_backend(Box::new(backend), color_support)
+    }
 
-        if let Err(err) = syscall_handler_join_result {
-            log::error!("{}", err);
-        }
+    /// Uses crossterm when its feature is enabled, even if termion is enabled
+    /// too through the default features.
+    #[cfg(feature = "crossterm-backend")]
+    pub fn new(color_support: ColorSupport) -> error::Result<Self> {
+        let backend = CrosstermBackend::new().map_err(Error::Terminal)?;
+        install_panic_hook(backend.panic_restorer());
+        Terminal::with_backend(Box::new(backend), color_support)
     }
-}
 
-impl Terminal {
-    pub fn new() -> Result<Self, io::Error> {
-        let raw_stdout = io::stdout().into_raw_mode()?;
+    /// Colors are quantized to what `color_support` allows when flushed.
+    pub fn with_backend(
+        backend: Box<dyn Backend>,
+        color_support: ColorSupport,
+    ) -> error::Result<Self> {
         let mut terminal = Terminal {
-            stdout: AlternateScreen::from(raw_stdout),
+            backend,
             size: ScreenSize {
                 width: u16::MIN,
                 height: u16::MIN,
             },
-            input_event_handler: InputEventHandler::new(),
-            syscall_signal_handler: SyscallHandler::new()?,
+            frame: Frame::new(u16::MIN, u16::MIN),
+            previous_frame: None,
+            cursor: (u16::MIN, u16::MIN),
+            style: Style::default(),
+            color_support,
+            cursor_visible: true,
+            flushed_cursor: None,
+            title: None,
+            flushed_title: None,
+            cursor_shape: CursorShape::default(),
+            flushed_cursor_shape: CursorShape::default(),
         };
         terminal.resize()?;
         Ok(terminal)
     }
 
-    pub fn flush(&mut self) -> Result<(), io::Error> {
-        self.stdout.flush()
+    pub fn flush(&mut self) -> error::Result<()> {
+        self.draw_frame().map_err(Error::Terminal)
     }
 
-    pub fn resize(&mut self) -> Result<(), io::Error> {
-        let (width, height) = termion::terminal_size()?;
+    /// Emits the changed cells as runs, moving the cursor and switching
+    /// styles only when the next run needs it. Nothing is written when
+    /// neither the cells nor the cursor changed.
+    fn draw_frame(&mut self) -> Result<(), io::Error> {
+        let runs = self.frame.diff(self.previous_frame.as_ref());
+        let cursor_state = (self.cursor, self.cursor_visible);
+        let title_changed = self.title != self.flushed_title;
+        let shape_changed = self.cursor_shape != self.flushed_cursor_shape;
+        if runs.is_empty()
+            && !title_changed
+            && !shape_changed
+            && self.flushed_cursor == Some(cursor_state)
+        {
+            return Ok(());
+        }
+
+        if title_changed {
+            if let Some(title) = &self.title {
+                self.backend.set_title(title)?;
+            }
+            self.flushed_title.clone_from(&self.title);
+        }
+        if shape_changed {
+            self.backend.set_cursor_shape(self.cursor_shape)?;
+            self.flushed_cursor_shape = self.cursor_shape;
+        }
+
+        if !runs.is_empty() {
+            self.backend.cursor_hide()?;
+        }
+        let mut position = None;
+        let mut style = Style::default();
+        self.apply_style(style)?;
+        for run in &runs {
+            if position != Some((run.x, run.y)) {
+                self.backend.cursor_to_position(run.x, run.y)?;
+            }
+            if style != run.style {
+                self.apply_style(run.style)?;
+                style = run.style;
+            }
+            self.backend.print(&run.text)?;
+            let length = run.text.width() as u16;
+            position = Some((run.x.saturating_add(length), run.y));
+        }
+        if style != Style::default() {
+            self.apply_style(Style::default())?;
+        }
+
+        let (x, y) = self.cursor;
+        self.backend.cursor_to_position(x, y)?;
+        if self.cursor_visible {
+            self.backend.cursor_show()?;
+        } else {
+            self.backend.cursor_hide()?;
+        }
+        self.backend.flush()?;
+
+        self.previous_frame = Some(self.frame.clone());
+        self.flushed_cursor = Some(cursor_state);
+        Ok(())
+    }
+
+    /// Switches the backend to `style`, with colors quantized to the color
+    /// support. A monochrome terminal shows highlighted cells in reverse
+    /// video instead.
+    fn apply_style(&mut self, style: Style) -> Result<(), io::Error> {
+        let mut attributes = style.attributes;
+        if self.color_support == ColorSupport::Monochrome {
+            attributes.reverse |= style.highlight;
+            return self.backend.set_style(None, None, attributes);
+        }
+
+        let quantize = |rgb| self.color_support.quantize(rgb).unwrap_or(Color::Rgb(rgb));
+        self.backend.set_style(
+            style.background.map(quantize),
+            style.foreground.map(quantize),
+            attributes,
+        )
+    }
+
+    /// Reads the new screen size. The next flush redraws the whole screen.
+    pub fn resize(&mut self) -> error::Result<()> {
+        let (width, height) = self.backend.size().map_err(Error::Terminal)?;
         self.size = ScreenSize {
             width,
             height: height.saturating_sub(PADDING_BUTTON),
         };
+        self.frame = Frame::new(width, height);
+        self.previous_frame = None;
+        self.flushed_cursor = None;
         Ok(())
     }
 
@@ -129,152 +384,280 @@ impl Terminal {
         self.size.height
     }
 
-    pub fn cursor_hide() {
-        print!("{}", termion::cursor::Hide);
+    pub fn cursor_hide(&mut self) {
+        self.cursor_visible = false;
     }
 
-    pub fn cursor_show() {
-        print!("{}", termion::cursor::Show);
+    pub fn cursor_show(&mut self) {
+        self.cursor_visible = true;
     }
 
-    pub fn cursor_to_default_position() {
-        print!("{}", termion::cursor::Goto::default());
+    /// Sets the cursor shape on the next flush. The terminal goes back to
+    /// the user's shape when it is restored.
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.cursor_shape = shape;
     }
 
-    pub fn cursor_to_position(x: u16, y: u16) {
-        print!(
-            "{}",
-            termion::cursor::Goto(x.saturating_add(1), y.saturating_add(1),)
-        );
+    pub fn cursor_to_position(&mut self, x: u16, y: u16) {
+        self.cursor = (x, y);
     }
 
-    pub fn clear_current_line() {
-        print!("{}", termion::clear::CurrentLine);
+    pub fn print(&mut self, text: &str) {
+        let (mut x, y) = self.cursor;
+        for symbol in text.chars() {
+            x = x.saturating_add(self.frame.set(x, y, symbol, self.style));
+        }
+        self.cursor = (x, y);
     }
 
-    pub fn set_row_color(background_rgb_color: (u8, u8, u8), foreground_rgb_color: (u8, u8, u8)) {
-        print!(
-            "{}{}",
-            color::Bg(color::Rgb(
-                background_rgb_color.0,
-                background_rgb_color.1,
-                background_rgb_color.2
-            )),
-            color::Fg(color::Rgb(
-                foreground_rgb_color.0,
-                foreground_rgb_color.1,
-                foreground_rgb_color.2
-            ))
-        );
+    /// Prints `text` on the `row`-th line of `area`, clipped and padded with
+    /// spaces to the area width so neighbouring regions are left untouched.
+    pub fn print_in_area(&mut self, area: Rect, row: u16, text: &str) {
+        if row >= area.height {
+            return;
+        }
+        let width = area.width as usize;
+        let mut clipped = String::new();
+        let mut clipped_width = 0;
+        for symbol in text.chars() {
+            let symbol_width = symbol.width().unwrap_or(1);
+            if clipped_width + symbol_width > width {
+                break;
+            }
+            clipped.push(symbol);
+            clipped_width += symbol_width;
+        }
+        clipped.extend(iter::repeat_n(' ', width - clipped_width));
+        self.cursor_to_position(area.x, area.y.saturating_add(row));
+        self.print(&clipped);
     }
 
-    pub fn reset_line_color() {
-        print!("{}{}", color::Bg(color::Reset), color::Fg(color::Reset));
+    /// Sets the style of the text printed from now on.
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style;
     }
 
-    pub fn pull_event(&self) -> Result<TerminalEvent, RecvError> {
-        select! {
-            recv(self.syscall_signal_handler.syscall_event_receiver) -> event => {
-                Ok(TerminalEvent::Syscall(event?))
-            },
-            recv(self.input_event_handler.input_event_receiver) -> event => {
-                Ok(TerminalEvent::Input(event?))
-            }
-            default(Duration::from_secs(1)) => {
-                if let Some(input_event_handle) = &self.input_event_handler.join_handle {
-                    if input_event_handle.is_finished() {
-                        return Ok(TerminalEvent::Input(InputEvent::Key(KeyEvent::Exit)));
-                    }
-                }
-
-                if let Some(syscall_event_handle) = &self.syscall_signal_handler.join_handle {
-                    if syscall_event_handle.is_finished() {
-                        return Ok(TerminalEvent::Input(InputEvent::Key(KeyEvent::Exit)));
-                    }
-                }
-
-                Ok(TerminalEvent::Empty)
-            }
+    pub fn reset_style(&mut self) {
+        self.style = Style::default();
+    }
+
+    pub fn suspend(&mut self) -> error::Result<()> {
+        self.backend.suspend().map_err(Error::Terminal)
+    }
+
+    /// Restores the editor screen after `SIGCONT`. The size may have changed
+    /// while stopped and the screen content is lost, so the next flush
+    /// redraws everything. The title and cursor shape were restored on
+    /// suspend and are set again.
+    pub fn resume(&mut self) -> error::Result<()> {
+        self.backend.resume().map_err(Error::Terminal)?;
+        self.flushed_title = None;
+        self.flushed_cursor_shape = CursorShape::default();
+        self.resize()
+    }
+
+    /// Sets the window title on the next flush. The title from before the
+    /// editor started comes back when the terminal is restored.
+    pub fn set_title(&mut self, title: &str) {
+        if self.title.as_deref() != Some(title) {
+            self.title = Some(String::from(title));
         }
     }
+
+    pub fn pull_event(&mut self) -> error::Result<TerminalEvent> {
+        Ok(self.backend.pull_event()?)
+    }
+
+    pub fn try_pull_event(&mut self) -> Option<TerminalEvent> {
+        self.backend.try_pull_event()
+    }
+
+    pub fn schedule_timer(&mut self, timer: Timer, delay: Duration) {
+        self.backend.schedule_timer(timer, delay);
+    }
 }
 
-struct SyscallHandler {
-    join_handle: Option<JoinHandle<Result<(), Box<dyn error::Error + Send + Sync>>>>,
-    syscall_event_receiver: Receiver<SyscallEvent>,
-    signals_handle: Handle,
+/// Whether a backend saved the window title on the title stack and has to
+/// restore it along with the rest of the terminal state. Clones share the
+/// flag, so the panic hook of a backend sees what the backend did.
+#[derive(Debug, Default, Clone)]
+struct WindowTitle {
+    saved: Arc<AtomicBool>,
 }
 
-impl SyscallHandler {
-    fn new() -> Result<Self, io::Error> {
-        let (syscall_event_sender, syscall_event_receiver) = unbounded::<SyscallEvent>();
-        let mut signals = Signals::new([SIGWINCH])?;
-        let signals_handle = signals.handle();
-
-        let join_handle =
-            thread::spawn(move || -> Result<(), Box<dyn error::Error + Send + Sync>> {
-                for signal in signals.forever() {
-                    let signal_event = match signal {
-                        SIGWINCH => SyscallEvent::WindowSizeChanged,
-                        _ => SyscallEvent::Unsupported,
-                    };
-                    syscall_event_sender.send(signal_event)?;
-                }
-                Ok(())
-            });
+impl WindowTitle {
+    /// Returns the OSC sequence setting the window title to `title`,
+    /// preceded by saving the current title the first time.
+    fn sequence(&self, title: &str) -> String {
+        let save = if self.saved.swap(true, Ordering::SeqCst) {
+            ""
+        } else {
+            SAVE_TITLE_SEQUENCE
+        };
+        // Control characters would end the sequence early.
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        format!("{save}\x1b]2;{title}\x07")
+    }
 
-        Ok(SyscallHandler {
-            join_handle: Some(join_handle),
-            syscall_event_receiver,
-            signals_handle,
-        })
+    /// Returns the sequence restoring the saved window title, if it was
+    /// saved.
+    fn restore_sequence(&self) -> &'static str {
+        if self.saved.swap(false, Ordering::SeqCst) {
+            RESTORE_TITLE_SEQUENCE
+        } else {
+            ""
+        }
     }
 }
 
-struct InputEventHandler {
-    join_handle: Option<JoinHandle<Result<(), Box<dyn error::Error + Send + Sync>>>>,
-    input_event_receiver: Receiver<InputEvent>,
+/// How many columns `symbol` takes on screen: control characters are shown as
+/// one space, zero-width ones are dropped and double-width ones take two.
+pub fn symbol_width(symbol: char) -> usize {
+    match symbol.width() {
+        _ if symbol.is_control() => 1,
+        Some(0) => 0,
+        Some(2) => 2,
+        _ => 1,
+    }
 }
 
-impl InputEventHandler {
-    fn new() -> Self {
-        let (input_event_sender, input_event_receiver) = unbounded::<InputEvent>();
-        let join_handle =
-            thread::spawn(move || -> Result<(), Box<dyn error::Error + Send + Sync>> {
-                loop {
-                    let input_event = InputEventHandler::next_key()?;
-                    input_event_sender.send(input_event.clone())?;
-                    if let InputEvent::Key(KeyEvent::Exit) = input_event {
-                        break;
-                    }
-                }
-                Ok(())
-            });
+/// Restores the shell terminal state before the default hook prints the
+/// panic, so the message is readable and the terminal is not left in raw mode
+/// on the alternate screen.
+pub fn install_panic_hook(restore_terminal: impl Fn() + Send + Sync + 'static) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+}
 
-        InputEventHandler {
-            join_handle: Some(join_handle),
-            input_event_receiver,
-        }
+#[cfg(test)]
+mod tests {
+    use super::test_backend::TestBackend;
+    use super::*;
+
+    #[test]
+    fn flush_only_prints_changed_cells() {
+        let backend = TestBackend::new(10, 4);
+        let mut terminal =
+            Terminal::with_backend(Box::new(backend.clone()), ColorSupport::TrueColor).unwrap();
+        let area = Rect {
+            x: 0,
+            y: 0,
+            width: 10,
+            height: 4,
+        };
+
+        terminal.print_in_area(area, 0, "hello");
+        terminal.flush().unwrap();
+        assert_eq!(backend.printed_cells(), 40);
+
+        terminal.print_in_area(area, 0, "hello");
+        terminal.flush().unwrap();
+        assert_eq!(backend.printed_cells(), 40);
+
+        terminal.print_in_area(area, 0, "help");
+        terminal.set_style(Style {
+            background: Some((0, 0, 0)),
+            foreground: Some((255, 255, 255)),
+            ..Style::default()
+        });
+        terminal.print_in_area(area, 1, "x");
+        terminal.reset_style();
+        terminal.flush().unwrap();
+        assert_eq!(backend.printed_cells(), 42 + 10);
+        assert_eq!(backend.line(0), "help");
+        assert_eq!(backend.cell(0, 1).background, Some(Color::Rgb((0, 0, 0))));
+        assert_eq!(backend.cell(4, 1).background, Some(Color::Rgb((0, 0, 0))));
+        assert_eq!(backend.cell(3, 0).background, None);
+    }
+
+    #[test]
+    fn wide_symbols_do_not_cost_cursor_moves() {
+        let backend = TestBackend::new(10, 2);
+        let mut terminal =
+            Terminal::with_backend(Box::new(backend.clone()), ColorSupport::TrueColor).unwrap();
+        let area = Rect {
+            x: 0,
+            y: 0,
+            width: 10,
+            height: 2,
+        };
+        terminal.flush().unwrap();
+        let moves = backend.cursor_moves();
+
+        terminal.print_in_area(area, 0, "日本");
+        terminal.set_style(Style {
+            background: Some((0, 0, 0)),
+            ..Style::default()
+        });
+        terminal.cursor_to_position(4, 0);
+        terminal.print("x");
+        terminal.reset_style();
+        terminal.flush().unwrap();
+
+        assert_eq!(backend.cell(2, 0).symbol, '本');
+        assert_eq!(backend.cell(4, 0).symbol, 'x');
+        // One move to the start of the changes and one back to the cursor.
+        assert_eq!(backend.cursor_moves() - moves, 2);
     }
 
-    fn next_key() -> Result<InputEvent, io::Error> {
-        if let Some(event) = io::stdin().events().next() {
-            return match event? {
-                Event::Key(key_event) => match key_event {
-                    Key::Char(c) => Ok(InputEvent::Key(KeyEvent::Char(c))),
-                    Key::Up => Ok(InputEvent::Key(KeyEvent::Up)),
-                    Key::Down => Ok(InputEvent::Key(KeyEvent::Down)),
-                    Key::Left => Ok(InputEvent::Key(KeyEvent::Left)),
-                    Key::Right => Ok(InputEvent::Key(KeyEvent::Right)),
-                    Key::Backspace => Ok(InputEvent::Key(KeyEvent::Backspace)),
-                    Key::Ctrl(EXIT_CHARACTER) => Ok(InputEvent::Key(KeyEvent::Exit)),
-                    Key::Ctrl(SAVE_CHARACTER) => Ok(InputEvent::Key(KeyEvent::SaveDocument)),
-                    _ => Ok(InputEvent::Key(KeyEvent::Unsupported)),
+    #[test]
+    fn colors_follow_color_support() {
+        let area = Rect {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 2,
+        };
+        let draw = |color_support| {
+            let backend = TestBackend::new(4, 2);
+            let mut terminal =
+                Terminal::with_backend(Box::new(backend.clone()), color_support).unwrap();
+            terminal.set_style(Style {
+                background: Some((239, 239, 239)),
+                foreground: Some((63, 63, 63)),
+                attributes: Attributes {
+                    bold: true,
+                    ..Attributes::default()
                 },
-                _ => Ok(InputEvent::Unsupported),
-            };
-        }
+                highlight: true,
+            });
+            terminal.print_in_area(area, 0, "bar");
+            terminal.set_style(Style {
+                background: Some((250, 250, 250)),
+                ..Style::default()
+            });
+            terminal.print_in_area(area, 1, "text");
+            terminal.flush().unwrap();
+            (backend.cell(0, 0), backend.cell(0, 1))
+        };
 
-        Ok(InputEvent::Empty)
+        let (bar, text) = draw(ColorSupport::Ansi256);
+        assert_eq!(bar.background, Some(Color::Indexed(255)));
+        assert_eq!(bar.foreground, Some(Color::Indexed(238)));
+        assert_eq!(text.background, Some(Color::Indexed(231)));
+
+        // Only highlights are reversed, not text that merely has a background.
+        let (bar, text) = draw(ColorSupport::Monochrome);
+        assert_eq!(bar.background, None);
+        assert!(bar.attributes.reverse && bar.attributes.bold);
+        assert_eq!(text.background, None);
+        assert!(!text.attributes.reverse);
+    }
+
+    #[test]
+    fn title_is_saved_once_and_restored() {
+        let title = WindowTitle::default();
+        let panic_hook_title = title.clone();
+        assert_eq!(
+            title.sequence("a\x07b"),
+            format!("{SAVE_TITLE_SEQUENCE}\x1b]2;ab\x07")
+        );
+        assert_eq!(title.sequence("c"), "\x1b]2;c\x07");
+        assert_eq!(panic_hook_title.restore_sequence(), RESTORE_TITLE_SEQUENCE);
+        assert_eq!(title.restore_sequence(), "");
+        assert_eq!(WindowTitle::default().restore_sequence(), "");
     }
 }
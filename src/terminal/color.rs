@@ -0,0 +1,304 @@
+use super::frame::Rgb;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The standard xterm colors of the 16 color palette.
+const ANSI_16_PALETTE: [Rgb; 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+/// The channel values of the 6x6x6 color cube at indexes 16 to 231 of the
+/// 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+const CUBE_START: u8 = 16;
+/// The gray ramp at indexes 232 to 255 runs from 8 to 238 in steps of 10.
+const GRAY_START: u8 = 232;
+const GRAY_STEPS: u8 = 24;
+const TERMINFO_DIRS: [&str; 4] = [
+    "/etc/terminfo",
+    "/lib/terminfo",
+    "/usr/share/terminfo",
+    "/usr/lib/terminfo",
+];
+const TERMINFO_MAGIC: u16 = 0o432;
+const TERMINFO_EXTENDED_MAGIC: u16 = 0o1036;
+const TERMINFO_HEADER_SIZE: usize = 12;
+/// The position of `colors` among the numeric capabilities.
+const TERMINFO_COLORS_INDEX: usize = 13;
+/// `colors` values from this on mean direct RGB colors.
+const DIRECT_COLORS: i32 = 1 << 24;
+
+/// What the terminal can display, from full RGB down to no colors at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    /// No colors; highlighted cells are drawn in reverse video.
+    Monochrome,
+}
+
+/// A color in the form the terminal understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Rgb(Rgb),
+    /// An index into the terminal palette.
+    Indexed(u8),
+}
+
+impl ColorSupport {
+    /// Detects the color support from `NO_COLOR`, `COLORTERM`, the terminfo
+    /// entry of `TERM` and finally the name in `TERM`.
+    pub fn detect() -> Self {
+        let term = env::var("TERM").unwrap_or_default();
+        ColorSupport::from_environment(
+            env::var_os("NO_COLOR").is_some_and(|no_color| !no_color.is_empty()),
+            env::var("COLORTERM").ok().as_deref(),
+            &term,
+            terminfo_colors(&term),
+        )
+    }
+
+    fn from_environment(
+        no_color: bool,
+        colorterm: Option<&str>,
+        term: &str,
+        terminfo_colors: Option<i32>,
+    ) -> Self {
+        if no_color || term.is_empty() || term == "dumb" {
+            return ColorSupport::Monochrome;
+        }
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return ColorSupport::TrueColor;
+        }
+        match terminfo_colors {
+            Some(colors) if colors >= DIRECT_COLORS => ColorSupport::TrueColor,
+            Some(colors) if colors >= 256 => ColorSupport::Ansi256,
+            Some(colors) if colors >= 8 => ColorSupport::Ansi16,
+            Some(_) => ColorSupport::Monochrome,
+            None if term.contains("256color") => ColorSupport::Ansi256,
+            None => ColorSupport::Ansi16,
+        }
+    }
+
+    /// Maps `rgb` to the closest color the terminal can show. Returns `None`
+    /// for a monochrome terminal.
+    pub fn quantize(self, rgb: Rgb) -> Option<Color> {
+        match self {
+            ColorSupport::TrueColor => Some(Color::Rgb(rgb)),
+            ColorSupport::Ansi256 => Some(Color::Indexed(ansi_256(rgb))),
+            ColorSupport::Ansi16 => Some(Color::Indexed(closest(&ANSI_16_PALETTE, rgb))),
+            ColorSupport::Monochrome => None,
+        }
+    }
+}
+
+/// Returns the basic SGR sequence for the first 16 palette colors, which
+/// 16 color terminals understand unlike the `38;5;n` form of the others.
+pub fn basic_color_sequence(color: Color, background: bool) -> Option<String> {
+    let Color::Indexed(index @ 0..16) = color else {
+        return None;
+    };
+    // Colors 8 to 15 are the bright variants at 90 to 97 and 100 to 107.
+    let code = match (background, index < 8) {
+        (false, true) => 30 + index,
+        (false, false) => 90 + index - 8,
+        (true, true) => 40 + index,
+        (true, false) => 100 + index - 8,
+    };
+    Some(format!("\x1b[{code}m"))
+}
+
+/// Picks the closer of the nearest color cube entry and the nearest gray.
+fn ansi_256(rgb: Rgb) -> u8 {
+    let (r, g, b) = rgb;
+    let cube_index = |value: u8| closest_level(&CUBE_LEVELS, value);
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube_color = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(u16::from(GRAY_STEPS) - 1) as u8;
+    let gray_level = 8 + gray_step * 10;
+
+    if distance(rgb, (gray_level, gray_level, gray_level)) < distance(rgb, cube_color) {
+        GRAY_START + gray_step
+    } else {
+        CUBE_START + (ri * 36 + gi * 6 + bi) as u8
+    }
+}
+
+fn closest_level(levels: &[u8], value: u8) -> usize {
+    (0..levels.len())
+        .min_by_key(|index| levels[*index].abs_diff(value))
+        .unwrap_or_default()
+}
+
+fn closest(palette: &[Rgb], rgb: Rgb) -> u8 {
+    (0..palette.len())
+        .min_by_key(|index| distance(palette[*index], rgb))
+        .unwrap_or_default() as u8
+}
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    let channel = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+}
+
+/// Reads the `colors` capability from the compiled terminfo entry of `term`.
+fn terminfo_colors(term: &str) -> Option<i32> {
+    let first = term.chars().next()?;
+    let home_terminfo = env::var_os("HOME").map(|home| PathBuf::from(home).join(".terminfo"));
+    let dirs_from_env = env::var("TERMINFO_DIRS").unwrap_or_default();
+    let dirs = env::var_os("TERMINFO")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(home_terminfo)
+        .chain(
+            dirs_from_env
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        )
+        .chain(TERMINFO_DIRS.iter().map(PathBuf::from));
+
+    for dir in dirs {
+        // Entries are grouped by their first letter, or its hex code on macOS.
+        for group in [first.to_string(), format!("{:x}", u32::from(first))] {
+            if let Ok(entry) = fs::read(dir.join(group).join(term)) {
+                return parse_terminfo_colors(&entry);
+            }
+        }
+    }
+    None
+}
+
+/// Finds `colors` in the numbers section of a compiled terminfo entry, see
+/// term(5).
+fn parse_terminfo_colors(entry: &[u8]) -> Option<i32> {
+    let read_u16 = |offset: usize| {
+        entry
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let number_size = match read_u16(0)? {
+        TERMINFO_MAGIC => 2,
+        TERMINFO_EXTENDED_MAGIC => 4,
+        _ => return None,
+    };
+    let names_size = usize::from(read_u16(2)?);
+    let booleans_count = usize::from(read_u16(4)?);
+    let numbers_count = usize::from(read_u16(6)?);
+    if numbers_count <= TERMINFO_COLORS_INDEX {
+        return None;
+    }
+
+    // The numbers start on an even byte.
+    let numbers_offset = (TERMINFO_HEADER_SIZE + names_size + booleans_count).next_multiple_of(2);
+    let offset = numbers_offset + TERMINFO_COLORS_INDEX * number_size;
+    let bytes = entry.get(offset..offset + number_size)?;
+    let colors = if number_size == 2 {
+        i32::from(i16::from_le_bytes([bytes[0], bytes[1]]))
+    } else {
+        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+    // Negative values mark an absent capability.
+    (colors >= 0).then_some(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_support_from_environment() {
+        let detect = ColorSupport::from_environment;
+        assert_eq!(
+            detect(false, Some("truecolor"), "xterm", Some(8)),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            detect(false, None, "screen", Some(256)),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(detect(false, None, "screen", Some(8)), ColorSupport::Ansi16);
+        assert_eq!(
+            detect(false, None, "xterm-256color", None),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            detect(false, None, "vt100", Some(0)),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(
+            detect(true, Some("truecolor"), "xterm", None),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(detect(false, None, "dumb", None), ColorSupport::Monochrome);
+    }
+
+    #[test]
+    fn quantizes_to_palettes() {
+        assert_eq!(
+            ColorSupport::Ansi256.quantize((255, 0, 0)),
+            Some(Color::Indexed(196))
+        );
+        assert_eq!(
+            ColorSupport::Ansi256.quantize((0, 95, 135)),
+            Some(Color::Indexed(24))
+        );
+        assert_eq!(
+            ColorSupport::Ansi256.quantize((239, 239, 239)),
+            Some(Color::Indexed(255))
+        );
+        assert_eq!(
+            ColorSupport::Ansi16.quantize((239, 239, 239)),
+            Some(Color::Indexed(7))
+        );
+        assert_eq!(
+            ColorSupport::Ansi16.quantize((63, 63, 63)),
+            Some(Color::Indexed(0))
+        );
+        assert_eq!(ColorSupport::Monochrome.quantize((63, 63, 63)), None);
+    }
+
+    #[test]
+    fn parses_colors_from_terminfo_entry() {
+        let name = b"test|a test entry\0";
+        let mut entry = vec![];
+        for value in [TERMINFO_EXTENDED_MAGIC, name.len() as u16, 1, 14, 0, 0] {
+            entry.extend_from_slice(&value.to_le_bytes());
+        }
+        entry.extend_from_slice(name);
+        entry.push(1);
+        if entry.len() % 2 == 1 {
+            entry.push(0);
+        }
+        for index in 0..14 {
+            let value: i32 = if index == TERMINFO_COLORS_INDEX {
+                256
+            } else {
+                -1
+            };
+            entry.extend_from_slice(&value.to_le_bytes());
+        }
+
+        assert_eq!(parse_terminfo_colors(&entry), Some(256));
+        assert_eq!(parse_terminfo_colors(&entry[..entry.len() - 2]), None);
+        assert_eq!(parse_terminfo_colors(b"not terminfo"), None);
+    }
+}
@@ -0,0 +1,360 @@
+use super::color::basic_color_sequence;
+use super::events::{EventLoop, InputSource};
+use super::{
+    keymap, Attributes, Backend, Color, CursorShape, InputEvent, KeyEvent, Modifiers, MouseButton,
+    MouseEvent, MouseEventKind, TerminalEvent, Timer, WindowTitle,
+};
+use crossbeam::channel::RecvError;
+use crossterm::cursor::{self, SetCursorStyle};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{
+    self, Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor,
+};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use signal_hook::consts::signal::SIGTSTP;
+use signal_hook::low_level;
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::thread;
+use std::time::Duration;
+
+pub struct CrosstermBackend {
+    stdout: io::Stdout,
+    keyboard_enhancement: bool,
+    event_loop: EventLoop,
+    title: WindowTitle,
+}
+
+impl Drop for CrosstermBackend {
+    fn drop(&mut self) {
+        // The panic hook has restored the terminal already, doing it again
+        // would move the cursor back over the printed panic message.
+        if thread::panicking() {
+            return;
+        }
+        if let Err(err) = self.restore() {
+            log::error!("{}", err);
+        }
+    }
+}
+
+impl CrosstermBackend {
+    pub fn new() -> Result<Self, io::Error> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+
+        // The support query reads the terminal reply from stdin, so it has to
+        // run before the event loop starts.
+        let keyboard_enhancement = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        let mut backend = CrosstermBackend {
+            stdout,
+            keyboard_enhancement,
+            event_loop: EventLoop::new(StdinSource)?,
+            title: WindowTitle::default(),
+        };
+        backend.push_keyboard_enhancement()?;
+        Ok(backend)
+    }
+
+    /// Returns the function for `install_panic_hook` restoring the terminal
+    /// this backend set up.
+    pub fn panic_restorer(&self) -> impl Fn() + Send + Sync + 'static {
+        let title = self.title.clone();
+        move || restore_terminal(&title)
+    }
+
+    fn push_keyboard_enhancement(&mut self) -> Result<(), io::Error> {
+        if self.keyboard_enhancement {
+            execute!(
+                self.stdout,
+                PushKeyboardEnhancementFlags(
+                    KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                        | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS
+                )
+            )?;
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<(), io::Error> {
+        if self.keyboard_enhancement {
+            queue!(self.stdout, PopKeyboardEnhancementFlags)?;
+        }
+        execute!(
+            self.stdout,
+            DisableMouseCapture,
+            cursor::Show,
+            SetCursorStyle::DefaultUserShape,
+            SetAttribute(Attribute::Reset),
+            ResetColor,
+            LeaveAlternateScreen,
+            Print(self.title.restore_sequence())
+        )?;
+        terminal::disable_raw_mode()
+    }
+}
+
+/// Puts the terminal back into the state it had before the editor started.
+/// Used by the panic hook, so errors are ignored.
+fn restore_terminal(title: &WindowTitle) {
+    let _ = execute!(
+        io::stdout(),
+        PopKeyboardEnhancementFlags,
+        DisableMouseCapture,
+        cursor::Show,
+        SetCursorStyle::DefaultUserShape,
+        SetAttribute(Attribute::Reset),
+        ResetColor,
+        LeaveAlternateScreen,
+        Print(title.restore_sequence())
+    );
+    let _ = terminal::disable_raw_mode();
+}
+
+impl Backend for CrosstermBackend {
+    fn size(&self) -> Result<(u16, u16), io::Error> {
+        terminal::size()
+    }
+
+    fn cursor_hide(&mut self) -> Result<(), io::Error> {
+        queue!(self.stdout, cursor::Hide)
+    }
+
+    fn cursor_show(&mut self) -> Result<(), io::Error> {
+        queue!(self.stdout, cursor::Show)
+    }
+
+    fn cursor_to_position(&mut self, x: u16, y: u16) -> Result<(), io::Error> {
+        queue!(self.stdout, cursor::MoveTo(x, y))
+    }
+
+    fn set_style(
+        &mut self,
+        background: Option<Color>,
+        foreground: Option<Color>,
+        attributes: Attributes,
+    ) -> Result<(), io::Error> {
+        queue!(self.stdout, SetAttribute(Attribute::Reset))?;
+        for (enabled, attribute) in [
+            (attributes.bold, Attribute::Bold),
+            (attributes.italic, Attribute::Italic),
+            (attributes.underline, Attribute::Underlined),
+            (attributes.reverse, Attribute::Reverse),
+        ] {
+            if enabled {
+                queue!(self.stdout, SetAttribute(attribute))?;
+            }
+        }
+        if let Some(background) = background {
+            queue_color(&mut self.stdout, background, true)?;
+        }
+        if let Some(foreground) = foreground {
+            queue_color(&mut self.stdout, foreground, false)?;
+        }
+        Ok(())
+    }
+
+    fn print(&mut self, text: &str) -> Result<(), io::Error> {
+        queue!(self.stdout, Print(text))
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        self.stdout.flush()
+    }
+
+    fn suspend(&mut self) -> Result<(), io::Error> {
+        self.restore()?;
+        low_level::raise(SIGTSTP)
+    }
+
+    fn resume(&mut self) -> Result<(), io::Error> {
+        terminal::enable_raw_mode()?;
+        execute!(self.stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        self.push_keyboard_enhancement()
+    }
+
+    fn pull_event(&mut self) -> Result<TerminalEvent, RecvError> {
+        self.event_loop.pull_event()
+    }
+
+    fn try_pull_event(&mut self) -> Option<TerminalEvent> {
+        self.event_loop.try_pull_event()
+    }
+
+    fn schedule_timer(&mut self, timer: Timer, delay: Duration) {
+        self.event_loop.schedule_timer(timer, delay);
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), io::Error> {
+        queue!(self.stdout, Print(self.title.sequence(title)))
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), io::Error> {
+        let style = match shape {
+            CursorShape::Default => SetCursorStyle::DefaultUserShape,
+            CursorShape::Block { blinking: true } => SetCursorStyle::BlinkingBlock,
+            CursorShape::Block { blinking: false } => SetCursorStyle::SteadyBlock,
+            CursorShape::Underline { blinking: true } => SetCursorStyle::BlinkingUnderScore,
+            CursorShape::Underline { blinking: false } => SetCursorStyle::SteadyUnderScore,
+            CursorShape::Bar { blinking: true } => SetCursorStyle::BlinkingBar,
+            CursorShape::Bar { blinking: false } => SetCursorStyle::SteadyBar,
+        };
+        queue!(self.stdout, style)
+    }
+}
+
+/// crossterm reads and decodes stdin itself, including telling a lone escape
+/// byte from the start of a sequence.
+struct StdinSource;
+
+impl InputSource for StdinSource {
+    fn raw_fd(&self) -> RawFd {
+        io::stdin().as_raw_fd()
+    }
+
+    fn read_events(&mut self, events: &mut Vec<InputEvent>) -> Result<(), io::Error> {
+        // Everything crossterm has parsed is taken, as events left in its
+        // buffer would not make stdin readable again.
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                Event::Key(key_event) if key_event.kind == KeyEventKind::Release => (),
+                Event::Key(key_event) => events.push(InputEvent::Key(map_key(
+                    key_event.code,
+                    key_event.modifiers,
+                ))),
+                Event::Mouse(mouse_event) => events.push(map_mouse_event(mouse_event)),
+                // Window size changes are reported through SIGWINCH like with
+                // termion.
+                _ => events.push(InputEvent::Unsupported),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Queues the sequence selecting `color`. crossterm sends even its named
+/// colors as `38;5;n`, so the basic ones are written directly.
+fn queue_color(out: &mut impl Write, color: Color, background: bool) -> Result<(), io::Error> {
+    if let Some(sequence) = basic_color_sequence(color, background) {
+        return queue!(out, Print(sequence));
+    }
+    if background {
+        queue!(out, SetBackgroundColor(map_color(color)))
+    } else {
+        queue!(out, SetForegroundColor(map_color(color)))
+    }
+}
+
+fn map_color(color: Color) -> style::Color {
+    match color {
+        Color::Rgb((r, g, b)) => style::Color::Rgb { r, g, b },
+        Color::Indexed(index) => style::Color::AnsiValue(index),
+    }
+}
+
+fn map_key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+    let alt = modifiers.contains(KeyModifiers::ALT);
+    match code {
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+            keymap::ctrl_key(c.to_ascii_lowercase())
+        },
+        KeyCode::Char(c) if alt => keymap::alt_key(c),
+        KeyCode::Char(c) => KeyEvent::Char(c),
+        KeyCode::Enter => KeyEvent::Char('\n'),
+        KeyCode::Tab => KeyEvent::Char('\t'),
+        KeyCode::Up if alt => KeyEvent::FocusUpperWindow,
+        KeyCode::Down if alt => KeyEvent::FocusLowerWindow,
+        KeyCode::Left if alt => KeyEvent::FocusLeftWindow,
+        KeyCode::Right if alt => KeyEvent::FocusRightWindow,
+        KeyCode::Up => KeyEvent::Up,
+        KeyCode::Down => KeyEvent::Down,
+        KeyCode::Left => KeyEvent::Left,
+        KeyCode::Right => KeyEvent::Right,
+        KeyCode::Backspace => KeyEvent::Backspace,
+        KeyCode::Insert => KeyEvent::ToggleOverwrite,
+        KeyCode::F(1) => KeyEvent::Help,
+        KeyCode::Esc => KeyEvent::Escape,
+        _ => KeyEvent::Unsupported,
+    }
+}
+
+fn map_mouse_event(mouse_event: event::MouseEvent) -> InputEvent {
+    let kind = match mouse_event.kind {
+        event::MouseEventKind::Down(button) => MouseEventKind::Press(map_mouse_button(button)),
+        event::MouseEventKind::Drag(event::MouseButton::Left) => MouseEventKind::Drag,
+        event::MouseEventKind::Up(_) => MouseEventKind::Release,
+        event::MouseEventKind::ScrollUp => MouseEventKind::ScrollUp,
+        event::MouseEventKind::ScrollDown => MouseEventKind::ScrollDown,
+        _ => return InputEvent::Unsupported,
+    };
+
+    InputEvent::Mouse(MouseEvent {
+        kind,
+        x: mouse_event.column,
+        y: mouse_event.row,
+        modifiers: Modifiers {
+            shift: mouse_event.modifiers.contains(KeyModifiers::SHIFT),
+            alt: mouse_event.modifiers.contains(KeyModifiers::ALT),
+            control: mouse_event.modifiers.contains(KeyModifiers::CONTROL),
+        },
+    })
+}
+
+fn map_mouse_button(button: event::MouseButton) -> MouseButton {
+    match button {
+        event::MouseButton::Left => MouseButton::Left,
+        event::MouseButton::Right => MouseButton::Right,
+        event::MouseButton::Middle => MouseButton::Middle,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::ColorSupport;
+
+    #[test]
+    fn maps_modified_keys() {
+        assert_eq!(
+            map_key(
+                KeyCode::Char('Q'),
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT
+            ),
+            KeyEvent::Exit
+        );
+        assert_eq!(
+            map_key(KeyCode::Char('v'), KeyModifiers::ALT),
+            KeyEvent::SplitVertical
+        );
+        assert_eq!(
+            map_key(KeyCode::Left, KeyModifiers::ALT),
+            KeyEvent::FocusLeftWindow
+        );
+        assert_eq!(
+            map_key(KeyCode::Char('a'), KeyModifiers::NONE),
+            KeyEvent::Char('a')
+        );
+    }
+
+    #[test]
+    fn sends_basic_sequences_for_16_colors() {
+        let sequence = |color, background| {
+            let mut out = vec![];
+            queue_color(&mut out, color, background).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let support = ColorSupport::Ansi16;
+        let red = support.quantize((205, 0, 0)).unwrap();
+        let bright_white = support.quantize((255, 255, 255)).unwrap();
+        assert_eq!(sequence(red, false), "\x1b[31m");
+        assert_eq!(sequence(red, true), "\x1b[41m");
+        assert_eq!(sequence(bright_white, false), "\x1b[97m");
+        assert_eq!(sequence(bright_white, true), "\x1b[107m");
+        assert_eq!(sequence(Color::Indexed(196), false), "\x1b[38;5;196m");
+    }
+}
@@ -0,0 +1,341 @@
+use super::{InputEvent, SyscallEvent, TerminalEvent, Timer};
+use crate::error::Error;
+use crossbeam::channel::{unbounded, Receiver, RecvError, Sender};
+use signal_hook::consts::signal::{SIGCONT, SIGHUP, SIGINT, SIGTERM, SIGWINCH};
+use signal_hook::low_level::{self, pipe};
+use signal_hook::{flag, SigId};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long an escape byte waits for the rest of a sequence before it is
+/// taken as the Escape key.
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Reads and decodes terminal input for the event loop.
+pub trait InputSource: Send {
+    /// The descriptor the event loop waits on for input.
+    fn raw_fd(&self) -> RawFd;
+    /// Reads the available input and appends the complete events to `events`.
+    /// Only called when `raw_fd` is readable, so it must not block.
+    fn read_events(&mut self, events: &mut Vec<InputEvent>) -> Result<(), io::Error>;
+    /// Returns whether bytes are held back as the possible start of an
+    /// escape sequence.
+    fn has_pending(&self) -> bool {
+        false
+    }
+    /// Decodes the held back bytes as they are, once nothing followed them
+    /// within the escape timeout.
+    fn flush_pending(&mut self, _events: &mut Vec<InputEvent>) {}
+}
+
+/// The thread waiting for input, signals and timers shared by the terminal
+/// backends. It sleeps in `poll` on the input descriptor and a self-pipe, so
+/// it only wakes up when there is something to report and stops as soon as
+/// the loop is dropped.
+pub struct EventLoop {
+    event_receiver: Receiver<TerminalEvent>,
+    timer_sender: Sender<(Instant, Timer)>,
+    waker: Arc<Waker>,
+    signal_ids: Vec<SigId>,
+    join_handle: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        for signal_id in self.signal_ids.drain(..) {
+            low_level::unregister(signal_id);
+        }
+        self.waker.shut_down.store(true, Ordering::SeqCst);
+        if let Err(err) = self.waker.wake() {
+            log::error!("Can't stop the event thread: {}", err);
+        }
+
+        let Some(join_handle) = self.join_handle.take() else {
+            return;
+        };
+        match join_handle.join() {
+            Ok(Ok(())) => (),
+            Ok(Err(err)) => log::error!("The event thread failed: {}", err),
+            Err(_) => log::error!("The event thread panicked"),
+        }
+    }
+}
+
+impl EventLoop {
+    pub fn new(input: impl InputSource + 'static) -> Result<Self, io::Error> {
+        let waker = Arc::new(Waker::new()?);
+        let (event_sender, event_receiver) = unbounded();
+        let (timer_sender, timer_receiver) = unbounded();
+
+        let mut signal_ids = vec![];
+        let mut signals = vec![];
+        for (signal, event) in [
+            (SIGWINCH, SyscallEvent::WindowSizeChanged),
+            (SIGCONT, SyscallEvent::Continued),
+            (SIGTERM, SyscallEvent::Terminated(SIGTERM)),
+            (SIGHUP, SyscallEvent::Terminated(SIGHUP)),
+            (SIGINT, SyscallEvent::Terminated(SIGINT)),
+        ] {
+            // Actions run in registration order, so the flag is set before
+            // the wake-up byte arrives. The pipe registration owns and closes
+            // its descriptor, hence the clone.
+            let raised = Arc::new(AtomicBool::new(false));
+            signal_ids.push(flag::register(signal, Arc::clone(&raised))?);
+            signal_ids.push(pipe::register(signal, waker.writer.try_clone()?)?);
+            signals.push((raised, event));
+        }
+
+        let event_thread = EventThread {
+            input,
+            waker: Arc::clone(&waker),
+            signals,
+            event_sender,
+            timer_receiver,
+            timers: vec![],
+            pending_since: None,
+        };
+
+        Ok(EventLoop {
+            event_receiver,
+            timer_sender,
+            waker,
+            signal_ids,
+            join_handle: Some(thread::spawn(move || event_thread.run())),
+        })
+    }
+
+    /// Waits for the next event. Fails once the event thread has stopped.
+    pub fn pull_event(&self) -> Result<TerminalEvent, RecvError> {
+        self.event_receiver.recv()
+    }
+
+    /// Returns an already queued event without waiting for one.
+    pub fn try_pull_event(&self) -> Option<TerminalEvent> {
+        self.event_receiver.try_recv().ok()
+    }
+
+    /// Makes the event thread send `TerminalEvent::Timer(timer)` after `delay`.
+    pub fn schedule_timer(&self, timer: Timer, delay: Duration) {
+        if self
+            .timer_sender
+            .send((Instant::now() + delay, timer))
+            .is_err()
+        {
+            return;
+        }
+        if let Err(err) = self.waker.wake() {
+            log::error!("Can't schedule a timer: {}", err);
+        }
+    }
+}
+
+struct EventThread<I> {
+    input: I,
+    waker: Arc<Waker>,
+    signals: Vec<(Arc<AtomicBool>, SyscallEvent)>,
+    event_sender: Sender<TerminalEvent>,
+    timer_receiver: Receiver<(Instant, Timer)>,
+    timers: Vec<(Instant, Timer)>,
+    pending_since: Option<Instant>,
+}
+
+impl<I: InputSource> EventThread<I> {
+    fn run(mut self) -> Result<(), Error> {
+        let mut input_events = vec![];
+        loop {
+            let (input_ready, woken) = self.wait()?;
+            if woken {
+                self.waker.drain()?;
+                if self.waker.shut_down.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+                for (raised, event) in &self.signals {
+                    if raised.swap(false, Ordering::SeqCst) {
+                        self.event_sender
+                            .send(TerminalEvent::Syscall(event.clone()))?;
+                    }
+                }
+                self.timers.extend(self.timer_receiver.try_iter());
+            }
+
+            if input_ready {
+                self.input
+                    .read_events(&mut input_events)
+                    .map_err(Error::Terminal)?;
+                self.pending_since = if self.input.has_pending() {
+                    self.pending_since.or_else(|| Some(Instant::now()))
+                } else {
+                    None
+                };
+            }
+
+            let now = Instant::now();
+            if self
+                .pending_since
+                .is_some_and(|since| since + ESCAPE_TIMEOUT <= now)
+            {
+                self.input.flush_pending(&mut input_events);
+                self.pending_since = None;
+            }
+            for input_event in input_events.drain(..) {
+                self.event_sender.send(TerminalEvent::Input(input_event))?;
+            }
+
+            let (expired, waiting) = self
+                .timers
+                .drain(..)
+                .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+            self.timers = waiting;
+            for (_, timer) in expired {
+                self.event_sender.send(TerminalEvent::Timer(timer))?;
+            }
+        }
+    }
+
+    /// Blocks until there is input, the self-pipe was written to or the next
+    /// timer is due. Returns whether the input and the self-pipe are ready.
+    fn wait(&self) -> Result<(bool, bool), io::Error> {
+        let deadline = self
+            .timers
+            .iter()
+            .map(|(deadline, _)| *deadline)
+            .chain(self.pending_since.map(|since| since + ESCAPE_TIMEOUT))
+            .min();
+        // Rounded up, so the loop does not spin while less than a millisecond
+        // is left.
+        let timeout = deadline.map_or(-1, |deadline| {
+            let micros = deadline
+                .saturating_duration_since(Instant::now())
+                .as_micros();
+            i32::try_from(micros.div_ceil(1000)).unwrap_or(i32::MAX)
+        });
+
+        let mut poll_fds = [
+            libc::pollfd {
+                fd: self.input.raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: self.waker.reader.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        // SAFETY: `poll_fds` is a valid array of `pollfd` of the given length.
+        let ready = unsafe {
+            libc::poll(
+                poll_fds.as_mut_ptr(),
+                poll_fds.len() as libc::nfds_t,
+                timeout,
+            )
+        };
+        if ready == -1 {
+            let err = io::Error::last_os_error();
+            // A signal interrupted the wait, its byte is read on the next pass.
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok((false, false));
+            }
+            return Err(err);
+        }
+        Ok((poll_fds[0].revents != 0, poll_fds[1].revents != 0))
+    }
+}
+
+/// The self-pipe waking up the event thread for signals, new timers and the
+/// shutdown. Both ends are non-blocking, as signal handlers write to it.
+struct Waker {
+    shut_down: AtomicBool,
+    reader: UnixStream,
+    writer: UnixStream,
+}
+
+impl Waker {
+    fn new() -> Result<Self, io::Error> {
+        let (reader, writer) = UnixStream::pair()?;
+        reader.set_nonblocking(true)?;
+        writer.set_nonblocking(true)?;
+        Ok(Waker {
+            shut_down: AtomicBool::new(false),
+            reader,
+            writer,
+        })
+    }
+
+    fn wake(&self) -> Result<(), io::Error> {
+        match (&self.writer).write(&[0]) {
+            // A full pipe wakes the thread up just as well.
+            Err(err) if err.kind() != io::ErrorKind::WouldBlock => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn drain(&self) -> Result<(), io::Error> {
+        let mut buffer = [0; 64];
+        loop {
+            match (&self.reader).read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(_) => (),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Input that never becomes readable.
+    struct NoInput(UnixStream);
+
+    impl InputSource for NoInput {
+        fn raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+
+        fn read_events(&mut self, _events: &mut Vec<InputEvent>) -> Result<(), io::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn timers_fire_in_order_of_their_deadline() {
+        let (input, _other_end) = UnixStream::pair().unwrap();
+        let event_loop = EventLoop::new(NoInput(input)).unwrap();
+        event_loop.schedule_timer(Timer::MessageExpiry(2), Duration::from_millis(40));
+        event_loop.schedule_timer(Timer::MessageExpiry(1), Duration::from_millis(10));
+
+        for expected in [1, 2] {
+            match event_loop.pull_event().unwrap() {
+                TerminalEvent::Timer(Timer::MessageExpiry(id)) => assert_eq!(id, expected),
+                event => panic!("unexpected event {event:?}"),
+            }
+        }
+        assert!(event_loop.try_pull_event().is_none());
+    }
+
+    #[test]
+    fn dropping_stops_the_thread_without_input() {
+        let (input, _other_end) = UnixStream::pair().unwrap();
+        let event_loop = EventLoop::new(NoInput(input)).unwrap();
+        let (dropped_sender, dropped_receiver) = unbounded();
+        thread::spawn(move || {
+            drop(event_loop);
+            dropped_sender.send(()).unwrap();
+        });
+
+        // Dropping joins the thread, which would wait for input forever if
+        // it was not woken up.
+        assert!(dropped_receiver
+            .recv_timeout(Duration::from_secs(5))
+            .is_ok());
+    }
+}
@@ -0,0 +1,258 @@
+use unicode_width::UnicodeWidthStr;
+
+pub type Rgb = (u8, u8, u8);
+
+/// Fills the cell covered by the right half of a double-width symbol.
+const CONTINUATION: char = '\0';
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Attributes {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+/// How a cell is drawn. Colors left at `None` are the terminal defaults.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub background: Option<Rgb>,
+    pub foreground: Option<Rgb>,
+    pub attributes: Attributes,
+    /// Marks the cell out from the text, e.g. as selected. Drawn in reverse
+    /// video where there are no colors.
+    pub highlight: bool,
+}
+
+impl Style {
+    /// Returns `self` with the colors it leaves unset taken from `base`.
+    /// A cell drawn over a highlighted one stays highlighted.
+    pub fn over(self, base: Style) -> Style {
+        Style {
+            background: self.background.or(base.background),
+            foreground: self.foreground.or(base.foreground),
+            attributes: self.attributes,
+            highlight: self.highlight || base.highlight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: char,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            symbol: ' ',
+            style: Style::default(),
+        }
+    }
+}
+
+/// A run of changed cells on one line that can be printed in one go.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Run {
+    pub x: u16,
+    pub y: u16,
+    pub style: Style,
+    pub text: String,
+}
+
+/// The content of every screen cell as drawn by the editor.
+#[derive(Clone)]
+pub struct Frame {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+}
+
+impl Frame {
+    pub fn new(width: u16, height: u16) -> Self {
+        Frame {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+        }
+    }
+
+    /// Writes `symbol` into the cell at `x` and `y`, ignoring cells outside of
+    /// the frame, and returns how many columns it takes. Control characters
+    /// would move the real cursor, so they are stored as spaces, and
+    /// zero-width ones are dropped. A double-width symbol also covers the next
+    /// cell, or is stored as a space when there is none.
+    pub fn set(&mut self, x: u16, y: u16, symbol: char, style: Style) -> u16 {
+        let width = super::symbol_width(symbol) as u16;
+        if width == 0 {
+            return 0;
+        }
+        let symbol = if symbol.is_control() { ' ' } else { symbol };
+        if x >= self.width || y >= self.height {
+            return width;
+        }
+        let wide = width == 2 && x.saturating_add(1) < self.width;
+        let symbol = if width == 2 && !wide { ' ' } else { symbol };
+
+        self.split_wide_symbol(x, y);
+        let index = self.index(x, y);
+        self.cells[index] = Cell { symbol, style };
+        if wide {
+            self.split_wide_symbol(x + 1, y);
+            self.cells[index + 1] = Cell {
+                symbol: CONTINUATION,
+                style,
+            };
+        }
+        width
+    }
+
+    /// Blanks the other half of a double-width symbol when one of its halves
+    /// at `x` is about to be overwritten.
+    fn split_wide_symbol(&mut self, x: u16, y: u16) {
+        let index = self.index(x, y);
+        if self.cells[index].symbol == CONTINUATION {
+            self.cells[index - 1].symbol = ' ';
+        } else if x.saturating_add(1) < self.width && self.cells[index + 1].symbol == CONTINUATION {
+            self.cells[index + 1].symbol = ' ';
+        }
+    }
+
+    fn index(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Collects the cells that differ from `previous`, grouped into runs of
+    /// neighbouring cells sharing the same style. Every cell is part of the
+    /// diff when there is no previous frame or its size is different.
+    pub fn diff(&self, previous: Option<&Frame>) -> Vec<Run> {
+        let previous = previous
+            .filter(|previous| previous.width == self.width && previous.height == self.height);
+        let mut runs: Vec<Run> = vec![];
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            // The right half of a double-width symbol is printed along with
+            // its left half.
+            if cell.symbol == CONTINUATION
+                || previous.is_some_and(|previous| previous.cells[index] == *cell)
+            {
+                continue;
+            }
+
+            let x = (index % self.width as usize) as u16;
+            let y = (index / self.width as usize) as u16;
+            match runs.last_mut() {
+                Some(run)
+                    if run.y == y
+                        && run.style == cell.style
+                        && run.x as usize + run.text.width() == x as usize =>
+                {
+                    run.text.push(cell.symbol);
+                },
+                _ => runs.push(Run {
+                    x,
+                    y,
+                    style: cell.style,
+                    text: cell.symbol.to_string(),
+                }),
+            }
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLE: Style = Style {
+        background: Some((0, 0, 0)),
+        foreground: Some((255, 255, 255)),
+        attributes: Attributes {
+            bold: true,
+            italic: false,
+            underline: false,
+            reverse: false,
+        },
+        highlight: false,
+    };
+
+    #[test]
+    fn diff_groups_changed_cells_into_runs() {
+        let previous = Frame::new(6, 2);
+        let mut frame = previous.clone();
+        frame.set(1, 0, 'a', Style::default());
+        frame.set(2, 0, 'b', Style::default());
+        frame.set(3, 0, 'c', STYLE);
+        frame.set(0, 1, 'd', Style::default());
+        frame.set(2, 1, ' ', Style::default());
+
+        assert_eq!(
+            frame.diff(Some(&previous)),
+            vec![
+                Run {
+                    x: 1,
+                    y: 0,
+                    style: Style::default(),
+                    text: String::from("ab"),
+                },
+                Run {
+                    x: 3,
+                    y: 0,
+                    style: STYLE,
+                    text: String::from("c"),
+                },
+                Run {
+                    x: 0,
+                    y: 1,
+                    style: Style::default(),
+                    text: String::from("d"),
+                },
+            ]
+        );
+        assert!(frame.diff(Some(&frame.clone())).is_empty());
+        assert_eq!(frame.diff(None).len(), 4);
+    }
+
+    #[test]
+    fn double_width_symbols_cover_two_cells() {
+        let previous = Frame::new(5, 1);
+        let mut frame = previous.clone();
+        assert_eq!(frame.set(0, 0, '日', Style::default()), 2);
+        assert_eq!(frame.set(2, 0, 'a', Style::default()), 1);
+        assert_eq!(frame.set(3, 0, '\u{301}', Style::default()), 0);
+        assert_eq!(frame.set(4, 0, '本', Style::default()), 2);
+
+        assert_eq!(
+            frame.diff(Some(&previous)),
+            vec![Run {
+                x: 0,
+                y: 0,
+                style: Style::default(),
+                text: String::from("日a"),
+            }]
+        );
+
+        let previous = frame.clone();
+        frame.set(1, 0, 'b', STYLE);
+        assert_eq!(
+            frame.diff(Some(&previous)),
+            vec![
+                Run {
+                    x: 0,
+                    y: 0,
+                    style: Style::default(),
+                    text: String::from(" "),
+                },
+                Run {
+                    x: 1,
+                    y: 0,
+                    style: STYLE,
+                    text: String::from("b"),
+                },
+            ]
+        );
+    }
+}
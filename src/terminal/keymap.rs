@@ -0,0 +1,62 @@
+use super::KeyEvent;
+
+const EXIT_CHARACTER: char = 'q';
+const SAVE_CHARACTER: char = 's';
+const SAVE_AS_CHARACTER: char = 'a';
+const MATCHING_BRACKET_CHARACTER: char = 'b';
+const GOTO_CHARACTER: char = 'g';
+const OPEN_FILE_CHARACTER: char = 'o';
+const NEXT_BUFFER_CHARACTER: char = 'n';
+const PREVIOUS_BUFFER_CHARACTER: char = 'p';
+const LIST_BUFFERS_CHARACTER: char = 'l';
+const CLOSE_BUFFER_CHARACTER: char = 'w';
+const SUSPEND_CHARACTER: char = 'z';
+const SWITCH_THEME_CHARACTER: char = 't';
+const SPLIT_HORIZONTAL_CHARACTER: char = 's';
+const SPLIT_VERTICAL_CHARACTER: char = 'v';
+const CLOSE_WINDOW_CHARACTER: char = 'x';
+const WRITE_COPY_CHARACTER: char = 'w';
+const INSERT_FILE_CHARACTER: char = 'i';
+const GROW_WINDOW_CHARACTER: char = '+';
+const SHRINK_WINDOW_CHARACTER: char = '-';
+const FOCUS_LEFT_CHARACTER: char = 'h';
+const FOCUS_DOWN_CHARACTER: char = 'j';
+const FOCUS_UP_CHARACTER: char = 'k';
+const FOCUS_RIGHT_CHARACTER: char = 'l';
+
+/// Maps a character pressed together with Ctrl to its editor command.
+pub fn ctrl_key(c: char) -> KeyEvent {
+    match c {
+        EXIT_CHARACTER => KeyEvent::Exit,
+        SAVE_CHARACTER => KeyEvent::SaveDocument,
+        SAVE_AS_CHARACTER => KeyEvent::SaveAs,
+        MATCHING_BRACKET_CHARACTER => KeyEvent::JumpToMatchingBracket,
+        GOTO_CHARACTER => KeyEvent::GoTo,
+        OPEN_FILE_CHARACTER => KeyEvent::OpenFile,
+        NEXT_BUFFER_CHARACTER => KeyEvent::NextBuffer,
+        PREVIOUS_BUFFER_CHARACTER => KeyEvent::PreviousBuffer,
+        LIST_BUFFERS_CHARACTER => KeyEvent::ListBuffers,
+        CLOSE_BUFFER_CHARACTER => KeyEvent::CloseBuffer,
+        SUSPEND_CHARACTER => KeyEvent::Suspend,
+        SWITCH_THEME_CHARACTER => KeyEvent::SwitchTheme,
+        _ => KeyEvent::Unsupported,
+    }
+}
+
+/// Maps a character pressed together with Alt to its editor command.
+pub fn alt_key(c: char) -> KeyEvent {
+    match c {
+        SPLIT_HORIZONTAL_CHARACTER => KeyEvent::SplitHorizontal,
+        SPLIT_VERTICAL_CHARACTER => KeyEvent::SplitVertical,
+        CLOSE_WINDOW_CHARACTER => KeyEvent::CloseWindow,
+        WRITE_COPY_CHARACTER => KeyEvent::WriteCopy,
+        INSERT_FILE_CHARACTER => KeyEvent::InsertFile,
+        GROW_WINDOW_CHARACTER => KeyEvent::GrowWindow,
+        SHRINK_WINDOW_CHARACTER => KeyEvent::ShrinkWindow,
+        FOCUS_LEFT_CHARACTER => KeyEvent::FocusLeftWindow,
+        FOCUS_DOWN_CHARACTER => KeyEvent::FocusLowerWindow,
+        FOCUS_UP_CHARACTER => KeyEvent::FocusUpperWindow,
+        FOCUS_RIGHT_CHARACTER => KeyEvent::FocusRightWindow,
+        _ => KeyEvent::Unsupported,
+    }
+}
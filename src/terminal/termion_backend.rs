@@ -0,0 +1,443 @@
+use super::color::basic_color_sequence;
+use super::events::{EventLoop, InputSource};
+use super::{
+    keymap, Attributes, Backend, Color, CursorShape, InputEvent, KeyEvent, Modifiers, MouseButton,
+    MouseEvent, MouseEventKind, TerminalEvent, Timer, WindowTitle,
+};
+use crossbeam::channel::RecvError;
+use signal_hook::consts::signal::SIGTSTP;
+use signal_hook::low_level;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem::ManuallyDrop;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::slice;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use termion::event::{self, Event, Key};
+use termion::input::MouseTerminal;
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::{AlternateScreen, ToAlternateScreen, ToMainScreen};
+use termion::{color, cursor, style};
+
+// termion only sends these when a `MouseTerminal` is created or dropped.
+const ENTER_MOUSE_SEQUENCE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const EXIT_MOUSE_SEQUENCE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+const ESCAPE: u8 = 0x1b;
+/// termion has no sequence for the user's cursor shape (DECSCUSR 0).
+const DEFAULT_CURSOR_SHAPE_SEQUENCE: &str = "\x1b[0 q";
+
+/// The terminal attributes from before entering raw mode, for the panic hook.
+static ORIGINAL_TERMIOS: OnceLock<libc::termios> = OnceLock::new();
+
+pub struct TermionBackend {
+    // termion's wrappers unwrap their writes when dropped, which panics once
+    // the terminal is gone (e.g. after SIGHUP). They are never dropped and
+    // `restore` does their work instead.
+    stdout: ManuallyDrop<AlternateScreen<MouseTerminal<RawTerminal<io::Stdout>>>>,
+    event_loop: EventLoop,
+    title: WindowTitle,
+}
+
+impl Drop for TermionBackend {
+    fn drop(&mut self) {
+        // The panic hook has restored the terminal already, doing it again
+        // would move the cursor back over the printed panic message.
+        if thread::panicking() {
+            return;
+        }
+        if let Err(err) = self.restore() {
+            log::error!("{}", err);
+        }
+    }
+}
+
+impl TermionBackend {
+    pub fn new() -> Result<Self, io::Error> {
+        save_original_termios();
+        let raw_stdout = io::stdout().into_raw_mode()?;
+        Ok(TermionBackend {
+            stdout: ManuallyDrop::new(AlternateScreen::from(MouseTerminal::from(raw_stdout))),
+            event_loop: EventLoop::new(StdinSource::new()?)?,
+            title: WindowTitle::default(),
+        })
+    }
+
+    /// Returns the function for `install_panic_hook` restoring the terminal
+    /// this backend set up.
+    pub fn panic_restorer(&self) -> impl Fn() + Send + Sync + 'static {
+        let title = self.title.clone();
+        move || restore_terminal(&title)
+    }
+
+    fn restore(&mut self) -> Result<(), io::Error> {
+        write!(
+            self.stdout,
+            "{EXIT_MOUSE_SEQUENCE}{}{}{}{DEFAULT_CURSOR_SHAPE_SEQUENCE}{ToMainScreen}{}",
+            style::Reset,
+            color::Bg(color::Reset),
+            termion::cursor::Show,
+            self.title.restore_sequence()
+        )?;
+        self.stdout.flush()?;
+        self.stdout.suspend_raw_mode()
+    }
+}
+
+/// Puts the terminal back into the state it had before the editor started.
+/// Used by the panic hook, so errors are ignored.
+fn restore_terminal(title: &WindowTitle) {
+    let mut stdout = io::stdout();
+    let _ = write!(
+        stdout,
+        "{EXIT_MOUSE_SEQUENCE}{}{}{}{DEFAULT_CURSOR_SHAPE_SEQUENCE}{ToMainScreen}{}",
+        style::Reset,
+        color::Bg(color::Reset),
+        termion::cursor::Show,
+        title.restore_sequence()
+    );
+    let _ = stdout.flush();
+    if let Some(termios) = ORIGINAL_TERMIOS.get() {
+        // SAFETY: `termios` was filled in by `tcgetattr` for the same descriptor.
+        unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, termios) };
+    }
+}
+
+fn save_original_termios() {
+    // SAFETY: an all-zero `termios` is a valid value for `tcgetattr` to fill in.
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    // SAFETY: `termios` is a valid, writable `termios` struct.
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &raw mut termios) } == 0 {
+        let _ = ORIGINAL_TERMIOS.set(termios);
+    }
+}
+
+/// Unbuffered stdin decoded with termion's parser. `io::Stdin` is not used,
+/// because bytes sitting in its buffer would not wake up the event loop.
+struct StdinSource {
+    stdin: File,
+    decoder: InputDecoder,
+}
+
+impl StdinSource {
+    fn new() -> Result<Self, io::Error> {
+        Ok(StdinSource {
+            stdin: File::from(io::stdin().as_fd().try_clone_to_owned()?),
+            decoder: InputDecoder::default(),
+        })
+    }
+}
+
+impl InputSource for StdinSource {
+    fn raw_fd(&self) -> RawFd {
+        self.stdin.as_raw_fd()
+    }
+
+    fn read_events(&mut self, events: &mut Vec<InputEvent>) -> Result<(), io::Error> {
+        let mut buffer = [0; 1024];
+        let read = loop {
+            match self.stdin.read(&mut buffer) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                result => break result?,
+            }
+        };
+        if read == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        self.decoder.decode(&buffer[..read], events);
+        Ok(())
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.decoder.pending.is_empty()
+    }
+
+    fn flush_pending(&mut self, events: &mut Vec<InputEvent>) {
+        self.decoder.flush(events);
+    }
+}
+
+/// Splits raw input into events. An escape sequence cut off at the end of a
+/// read is kept until the rest arrives, or until the event loop gives up
+/// waiting and `flush` decodes it as it is, which turns a lone escape byte
+/// into the Escape key.
+#[derive(Default)]
+struct InputDecoder {
+    pending: Vec<u8>,
+}
+
+impl InputDecoder {
+    fn decode(&mut self, bytes: &[u8], events: &mut Vec<InputEvent>) {
+        self.pending.extend_from_slice(bytes);
+        self.parse(false, events);
+    }
+
+    fn flush(&mut self, events: &mut Vec<InputEvent>) {
+        self.parse(true, events);
+    }
+
+    fn parse(&mut self, complete: bool, events: &mut Vec<InputEvent>) {
+        let mut start = 0;
+        while start < self.pending.len() {
+            let first = self.pending[start];
+            let mut rest = PendingBytes {
+                bytes: self.pending[start + 1..].iter(),
+                exhausted: false,
+            };
+            let parsed = event::parse_event(first, &mut rest);
+            let consumed = self.pending.len() - start - rest.bytes.len();
+            match parsed {
+                Err(_) if rest.exhausted && !complete => break,
+                Err(_) if first == ESCAPE && consumed == 1 => {
+                    events.push(InputEvent::Key(KeyEvent::Escape));
+                },
+                Err(_) => events.push(InputEvent::Unsupported),
+                Ok(event) => events.push(map_event(&event)),
+            }
+            start += consumed;
+        }
+        self.pending.drain(..start);
+    }
+}
+
+/// Feeds the pending bytes to termion's parser and notes whether it asked for
+/// more bytes than there are.
+struct PendingBytes<'a> {
+    bytes: slice::Iter<'a, u8>,
+    exhausted: bool,
+}
+
+impl Iterator for PendingBytes<'_> {
+    type Item = Result<u8, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let byte = self.bytes.next();
+        if byte.is_none() {
+            self.exhausted = true;
+        }
+        byte.map(|byte| Ok(*byte))
+    }
+}
+
+impl Backend for TermionBackend {
+    fn size(&self) -> Result<(u16, u16), io::Error> {
+        termion::terminal_size()
+    }
+
+    fn cursor_hide(&mut self) -> Result<(), io::Error> {
+        write!(self.stdout, "{}", termion::cursor::Hide)
+    }
+
+    fn cursor_show(&mut self) -> Result<(), io::Error> {
+        write!(self.stdout, "{}", termion::cursor::Show)
+    }
+
+    fn cursor_to_position(&mut self, x: u16, y: u16) -> Result<(), io::Error> {
+        write!(
+            self.stdout,
+            "{}",
+            termion::cursor::Goto(x.saturating_add(1), y.saturating_add(1))
+        )
+    }
+
+    fn set_style(
+        &mut self,
+        background: Option<Color>,
+        foreground: Option<Color>,
+        attributes: Attributes,
+    ) -> Result<(), io::Error> {
+        write!(self.stdout, "{}", style::Reset)?;
+        for (enabled, sequence) in [
+            (attributes.bold, &style::Bold as &dyn fmt::Display),
+            (attributes.italic, &style::Italic),
+            (attributes.underline, &style::Underline),
+            (attributes.reverse, &style::Invert),
+        ] {
+            if enabled {
+                write!(self.stdout, "{sequence}")?;
+            }
+        }
+        if let Some(background) = background {
+            write_color(&mut *self.stdout, background, true)?;
+        }
+        if let Some(foreground) = foreground {
+            write_color(&mut *self.stdout, foreground, false)?;
+        }
+        Ok(())
+    }
+
+    fn print(&mut self, text: &str) -> Result<(), io::Error> {
+        self.stdout.write_all(text.as_bytes())
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        self.stdout.flush()
+    }
+
+    fn suspend(&mut self) -> Result<(), io::Error> {
+        self.restore()?;
+        low_level::raise(SIGTSTP)
+    }
+
+    fn resume(&mut self) -> Result<(), io::Error> {
+        self.stdout.activate_raw_mode()?;
+        write!(self.stdout, "{ToAlternateScreen}{ENTER_MOUSE_SEQUENCE}")?;
+        self.stdout.flush()
+    }
+
+    fn pull_event(&mut self) -> Result<TerminalEvent, RecvError> {
+        self.event_loop.pull_event()
+    }
+
+    fn try_pull_event(&mut self) -> Option<TerminalEvent> {
+        self.event_loop.try_pull_event()
+    }
+
+    fn schedule_timer(&mut self, timer: Timer, delay: Duration) {
+        self.event_loop.schedule_timer(timer, delay);
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), io::Error> {
+        write!(self.stdout, "{}", self.title.sequence(title))
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), io::Error> {
+        let sequence: &dyn fmt::Display = match shape {
+            CursorShape::Default => &DEFAULT_CURSOR_SHAPE_SEQUENCE,
+            CursorShape::Block { blinking: true } => &cursor::BlinkingBlock,
+            CursorShape::Block { blinking: false } => &cursor::SteadyBlock,
+            CursorShape::Underline { blinking: true } => &cursor::BlinkingUnderline,
+            CursorShape::Underline { blinking: false } => &cursor::SteadyUnderline,
+            CursorShape::Bar { blinking: true } => &cursor::BlinkingBar,
+            CursorShape::Bar { blinking: false } => &cursor::SteadyBar,
+        };
+        write!(self.stdout, "{sequence}")
+    }
+}
+
+fn map_event(event: &Event) -> InputEvent {
+    match *event {
+        Event::Key(key_event) => InputEvent::Key(match key_event {
+            Key::Char(c) => KeyEvent::Char(c),
+            Key::Up => KeyEvent::Up,
+            Key::Down => KeyEvent::Down,
+            Key::Left => KeyEvent::Left,
+            Key::Right => KeyEvent::Right,
+            Key::Backspace => KeyEvent::Backspace,
+            Key::Insert => KeyEvent::ToggleOverwrite,
+            Key::F(1) => KeyEvent::Help,
+            Key::Esc => KeyEvent::Escape,
+            Key::Ctrl(c) => keymap::ctrl_key(c),
+            Key::Alt(c) => keymap::alt_key(c),
+            _ => KeyEvent::Unsupported,
+        }),
+        Event::Mouse(mouse_event) => map_mouse_event(mouse_event),
+        Event::Unsupported(_) => InputEvent::Unsupported,
+    }
+}
+
+fn map_mouse_event(mouse_event: event::MouseEvent) -> InputEvent {
+    let (kind, x, y) = match mouse_event {
+        event::MouseEvent::Press(button, x, y) => {
+            let kind = match button {
+                event::MouseButton::Left => MouseEventKind::Press(MouseButton::Left),
+                event::MouseButton::Right => MouseEventKind::Press(MouseButton::Right),
+                event::MouseButton::Middle => MouseEventKind::Press(MouseButton::Middle),
+                event::MouseButton::WheelUp => MouseEventKind::ScrollUp,
+                event::MouseButton::WheelDown => MouseEventKind::ScrollDown,
+            };
+            (kind, x, y)
+        },
+        event::MouseEvent::Hold(x, y) => (MouseEventKind::Drag, x, y),
+        event::MouseEvent::Release(x, y) => (MouseEventKind::Release, x, y),
+    };
+
+    // termion reports 1-based coordinates.
+    InputEvent::Mouse(MouseEvent {
+        kind,
+        x: x.saturating_sub(1),
+        y: y.saturating_sub(1),
+        modifiers: Modifiers::default(),
+    })
+}
+
+/// Writes the sequence selecting `color`. termion sends even its named
+/// colors as `38;5;n`, so the basic ones are written directly.
+fn write_color(out: &mut impl Write, color: Color, background: bool) -> Result<(), io::Error> {
+    if let Some(sequence) = basic_color_sequence(color, background) {
+        return out.write_all(sequence.as_bytes());
+    }
+    match (color, background) {
+        (Color::Rgb((r, g, b)), true) => write!(out, "{}", color::Bg(color::Rgb(r, g, b))),
+        (Color::Rgb((r, g, b)), false) => write!(out, "{}", color::Fg(color::Rgb(r, g, b))),
+        (Color::Indexed(index), true) => write!(out, "{}", color::Bg(color::AnsiValue(index))),
+        (Color::Indexed(index), false) => write!(out, "{}", color::Fg(color::AnsiValue(index))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::ColorSupport;
+
+    fn keys(events: &[InputEvent]) -> Vec<KeyEvent> {
+        events
+            .iter()
+            .map(|event| match event {
+                InputEvent::Key(key) => *key,
+                _ => KeyEvent::Unsupported,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn waits_for_the_rest_of_a_split_sequence() {
+        let mut decoder = InputDecoder::default();
+        let mut events = vec![];
+        decoder.decode(b"a\x1b[", &mut events);
+        assert_eq!(keys(&events), [KeyEvent::Char('a')]);
+
+        decoder.decode(b"A\xc3", &mut events);
+        decoder.decode(b"\xa9", &mut events);
+        assert_eq!(
+            keys(&events),
+            [KeyEvent::Char('a'), KeyEvent::Up, KeyEvent::Char('\u{e9}')]
+        );
+        assert!(decoder.pending.is_empty());
+    }
+
+    #[test]
+    fn lone_escape_is_the_escape_key_once_flushed() {
+        let mut decoder = InputDecoder::default();
+        let mut events = vec![];
+        decoder.decode(b"\x1b", &mut events);
+        assert!(events.is_empty());
+
+        decoder.flush(&mut events);
+        assert_eq!(keys(&events), [KeyEvent::Escape]);
+        assert!(decoder.pending.is_empty());
+
+        events.clear();
+        decoder.decode(b"\x1bs", &mut events);
+        assert_eq!(keys(&events), [KeyEvent::SplitHorizontal]);
+    }
+
+    #[test]
+    fn sends_basic_sequences_for_16_colors() {
+        let sequence = |color, background| {
+            let mut out = vec![];
+            write_color(&mut out, color, background).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let support = ColorSupport::Ansi16;
+        let red = support.quantize((205, 0, 0)).unwrap();
+        let bright_white = support.quantize((255, 255, 255)).unwrap();
+        assert_eq!(sequence(red, false), "\x1b[31m");
+        assert_eq!(sequence(red, true), "\x1b[41m");
+        assert_eq!(sequence(bright_white, false), "\x1b[97m");
+        assert_eq!(sequence(bright_white, true), "\x1b[107m");
+        assert_eq!(sequence(Color::Indexed(196), false), "\x1b[38;5;196m");
+    }
+}
@@ -0,0 +1,278 @@
+use super::{Attributes, Backend, Color, CursorShape, InputEvent, KeyEvent, TerminalEvent, Timer};
+use crossbeam::channel::RecvError;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use unicode_width::UnicodeWidthChar;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: char,
+    pub background: Option<Color>,
+    pub foreground: Option<Color>,
+    pub attributes: Attributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            symbol: ' ',
+            background: None,
+            foreground: None,
+            attributes: Attributes::default(),
+        }
+    }
+}
+
+struct TestScreen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    cursor: (u16, u16),
+    cursor_visible: bool,
+    style: (Option<Color>, Option<Color>, Attributes),
+    printed_cells: usize,
+    cursor_moves: usize,
+    flushes: usize,
+    suspended: bool,
+    events: VecDeque<TerminalEvent>,
+    panic_after_events: bool,
+    timers: Vec<(Timer, Duration)>,
+    title: Option<String>,
+    cursor_shape: CursorShape,
+}
+
+/// An in-memory backend recording what is drawn into a grid of cells and
+/// replaying scripted events. Clones share the same screen, so a test can keep
+/// one to inspect the output after handing the other to `Terminal`. Scheduled
+/// timers are only recorded, tests push their events like any other. Once the
+/// scripted events run out the backend reports `KeyEvent::Exit`, or panics
+/// when asked to with `panic_after_events`.
+#[derive(Clone)]
+pub struct TestBackend {
+    screen: Rc<RefCell<TestScreen>>,
+    /// Set by the restorer handed to the panic hook, which may run on any
+    /// thread.
+    restored_after_panic: Arc<AtomicBool>,
+}
+
+impl TestBackend {
+    pub fn new(width: u16, height: u16) -> Self {
+        TestBackend {
+            screen: Rc::new(RefCell::new(TestScreen {
+                width,
+                height,
+                cells: vec![Cell::default(); width as usize * height as usize],
+                cursor: (u16::MIN, u16::MIN),
+                cursor_visible: true,
+                style: (None, None, Attributes::default()),
+                printed_cells: 0,
+                cursor_moves: 0,
+                flushes: 0,
+                suspended: false,
+                events: VecDeque::new(),
+                panic_after_events: false,
+                timers: vec![],
+                title: None,
+                cursor_shape: CursorShape::default(),
+            })),
+            restored_after_panic: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn push_event(&self, event: TerminalEvent) {
+        self.screen.borrow_mut().events.push_back(event);
+    }
+
+    pub fn push_keys(&self, keys: &[KeyEvent]) {
+        for key in keys {
+            self.push_event(TerminalEvent::Input(InputEvent::Key(*key)));
+        }
+    }
+
+    pub fn push_text(&self, text: &str) {
+        for c in text.chars() {
+            self.push_event(TerminalEvent::Input(InputEvent::Key(KeyEvent::Char(c))));
+        }
+    }
+
+    /// Makes the backend panic instead of reporting `KeyEvent::Exit` once the
+    /// scripted events run out.
+    pub fn panic_after_events(&self) {
+        self.screen.borrow_mut().panic_after_events = true;
+    }
+
+    /// Returns the function for `install_panic_hook` restoring this backend.
+    pub fn panic_restorer(&self) -> impl Fn() + Send + Sync + 'static {
+        let restored = Arc::clone(&self.restored_after_panic);
+        move || restored.store(true, Ordering::SeqCst)
+    }
+
+    pub fn restored_after_panic(&self) -> bool {
+        self.restored_after_panic.load(Ordering::SeqCst)
+    }
+
+    pub fn resize(&self, width: u16, height: u16) {
+        let mut screen = self.screen.borrow_mut();
+        screen.width = width;
+        screen.height = height;
+        screen.cells = vec![Cell::default(); width as usize * height as usize];
+    }
+
+    pub fn cell(&self, x: u16, y: u16) -> Cell {
+        let screen = self.screen.borrow();
+        screen.cells[y as usize * screen.width as usize + x as usize]
+    }
+
+    /// Returns the `y`-th screen line with trailing spaces removed.
+    pub fn line(&self, y: u16) -> String {
+        let screen = self.screen.borrow();
+        let start = y as usize * screen.width as usize;
+        let line: String = screen.cells[start..start + screen.width as usize]
+            .iter()
+            .map(|cell| cell.symbol)
+            .collect();
+        String::from(line.trim_end())
+    }
+
+    pub fn cursor(&self) -> (u16, u16) {
+        self.screen.borrow().cursor
+    }
+
+    /// Returns how many cells were printed since the backend was created.
+    pub fn printed_cells(&self) -> usize {
+        self.screen.borrow().printed_cells
+    }
+
+    /// Returns how many times the cursor was moved since the backend was
+    /// created.
+    pub fn cursor_moves(&self) -> usize {
+        self.screen.borrow().cursor_moves
+    }
+
+    /// Returns how many frames were flushed since the backend was created.
+    pub fn flushes(&self) -> usize {
+        self.screen.borrow().flushes
+    }
+
+    pub fn suspended(&self) -> bool {
+        self.screen.borrow().suspended
+    }
+
+    pub fn scheduled_timers(&self) -> Vec<(Timer, Duration)> {
+        self.screen.borrow().timers.clone()
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.screen.borrow().cursor_visible
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.screen.borrow().title.clone()
+    }
+
+    pub fn cursor_shape(&self) -> CursorShape {
+        self.screen.borrow().cursor_shape
+    }
+}
+
+impl Backend for TestBackend {
+    fn size(&self) -> Result<(u16, u16), io::Error> {
+        let screen = self.screen.borrow();
+        Ok((screen.width, screen.height))
+    }
+
+    fn cursor_hide(&mut self) -> Result<(), io::Error> {
+        self.screen.borrow_mut().cursor_visible = false;
+        Ok(())
+    }
+
+    fn cursor_show(&mut self) -> Result<(), io::Error> {
+        self.screen.borrow_mut().cursor_visible = true;
+        Ok(())
+    }
+
+    fn cursor_to_position(&mut self, x: u16, y: u16) -> Result<(), io::Error> {
+        let mut screen = self.screen.borrow_mut();
+        screen.cursor = (x, y);
+        screen.cursor_moves += 1;
+        Ok(())
+    }
+
+    fn set_style(
+        &mut self,
+        background: Option<Color>,
+        foreground: Option<Color>,
+        attributes: Attributes,
+    ) -> Result<(), io::Error> {
+        self.screen.borrow_mut().style = (background, foreground, attributes);
+        Ok(())
+    }
+
+    fn print(&mut self, text: &str) -> Result<(), io::Error> {
+        let mut screen = self.screen.borrow_mut();
+        let (background, foreground, attributes) = screen.style;
+        for symbol in text.chars() {
+            let (x, y) = screen.cursor;
+            if x < screen.width && y < screen.height {
+                let index = y as usize * screen.width as usize + x as usize;
+                screen.cells[index] = Cell {
+                    symbol,
+                    background,
+                    foreground,
+                    attributes,
+                };
+            }
+            screen.cursor.0 = x.saturating_add(symbol.width().unwrap_or(1) as u16);
+            screen.printed_cells += 1;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        self.screen.borrow_mut().flushes += 1;
+        Ok(())
+    }
+
+    /// Only records the state; the test process is not stopped.
+    fn suspend(&mut self) -> Result<(), io::Error> {
+        self.screen.borrow_mut().suspended = true;
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), io::Error> {
+        self.screen.borrow_mut().suspended = false;
+        Ok(())
+    }
+
+    fn pull_event(&mut self) -> Result<TerminalEvent, RecvError> {
+        let mut screen = self.screen.borrow_mut();
+        match screen.events.pop_front() {
+            Some(event) => Ok(event),
+            None if screen.panic_after_events => panic!("the scripted events ran out"),
+            None => Ok(TerminalEvent::Input(InputEvent::Key(KeyEvent::Exit))),
+        }
+    }
+
+    fn try_pull_event(&mut self) -> Option<TerminalEvent> {
+        self.screen.borrow_mut().events.pop_front()
+    }
+
+    fn schedule_timer(&mut self, timer: Timer, delay: Duration) {
+        self.screen.borrow_mut().timers.push((timer, delay));
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), io::Error> {
+        self.screen.borrow_mut().title = Some(String::from(title));
+        Ok(())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), io::Error> {
+        self.screen.borrow_mut().cursor_shape = shape;
+        Ok(())
+    }
+}
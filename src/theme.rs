@@ -0,0 +1,357 @@
+use crate::config;
+use crate::error::{self, Error};
+use crate::highlighting::TokenType;
+use crate::terminal::{Rgb, Style};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+pub const DEFAULT_THEME: &str = "dark";
+const THEMES_DIR: &str = "themes";
+const THEME_FILE_EXTENSION: &str = "theme";
+/// Starts a theme from the styles of a built-in theme.
+const BASE_KEY: &str = "base";
+
+const ELEMENT_NAMES: [(&str, ThemeElement); 12] = [
+    ("text", ThemeElement::Text),
+    ("status_bar", ThemeElement::StatusBar),
+    ("inactive_status_bar", ThemeElement::InactiveStatusBar),
+    ("message_line", ThemeElement::MessageLine),
+    ("gutter", ThemeElement::Gutter),
+    ("selection", ThemeElement::Selection),
+    ("search_match", ThemeElement::SearchMatch),
+    ("cursor_line", ThemeElement::CursorLine),
+    ("ruler", ThemeElement::Ruler),
+    ("matching_bracket", ThemeElement::MatchingBracket),
+    ("syntax.string", ThemeElement::Syntax(TokenType::String)),
+    ("syntax.comment", ThemeElement::Syntax(TokenType::Comment)),
+];
+
+/// Built-in themes in the same format as user theme files: one
+/// `element = style` line per element, where the style lists `fg:#rrggbb`,
+/// `bg:#rrggbb` and the `bold`, `italic`, `underline` and `reverse`
+/// attributes.
+const BUILTIN_THEMES: [(&str, &str); 4] = [
+    (
+        "dark",
+        "# For dark terminals, keeping the terminal colors for text.
+status_bar = fg:#3f3f3f bg:#efefef
+inactive_status_bar = fg:#303030 bg:#9e9e9e
+gutter = fg:#6c6c6c
+selection = fg:#ffffff bg:#264f78
+search_match = fg:#000000 bg:#d7af00
+cursor_line = bg:#262626
+ruler = bg:#303030
+matching_bracket = fg:#ffffff bg:#005f87
+syntax.string = fg:#ce9178
+syntax.comment = fg:#6a9955 italic
+",
+    ),
+    (
+        "light",
+        "text = fg:#202020 bg:#fafafa
+status_bar = fg:#fafafa bg:#3c3c3c
+inactive_status_bar = fg:#202020 bg:#bcbcbc
+gutter = fg:#9e9e9e bg:#eeeeee
+selection = bg:#add6ff
+search_match = bg:#ffd75f
+cursor_line = bg:#eeeeee
+ruler = bg:#e4e4e4
+matching_bracket = fg:#fafafa bg:#0087af bold
+syntax.string = fg:#a31515
+syntax.comment = fg:#008000 italic
+",
+    ),
+    (
+        "solarized-dark",
+        "text = fg:#839496 bg:#002b36
+status_bar = fg:#fdf6e3 bg:#586e75
+inactive_status_bar = fg:#839496 bg:#073642
+message_line = fg:#93a1a1
+gutter = fg:#586e75 bg:#073642
+selection = fg:#fdf6e3 bg:#268bd2
+search_match = fg:#002b36 bg:#b58900
+cursor_line = bg:#073642
+ruler = bg:#073642
+matching_bracket = fg:#002b36 bg:#2aa198 bold
+syntax.string = fg:#2aa198
+syntax.comment = fg:#586e75 italic
+",
+    ),
+    (
+        "solarized-light",
+        "text = fg:#657b83 bg:#fdf6e3
+status_bar = fg:#fdf6e3 bg:#657b83
+inactive_status_bar = fg:#586e75 bg:#eee8d5
+message_line = fg:#586e75
+gutter = fg:#93a1a1 bg:#eee8d5
+selection = fg:#fdf6e3 bg:#268bd2
+search_match = fg:#fdf6e3 bg:#b58900
+cursor_line = bg:#eee8d5
+ruler = bg:#eee8d5
+matching_bracket = fg:#fdf6e3 bg:#2aa198 bold
+syntax.string = fg:#2aa198
+syntax.comment = fg:#93a1a1 italic
+",
+    ),
+];
+
+/// A part of the screen with its own style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThemeElement {
+    Text,
+    StatusBar,
+    InactiveStatusBar,
+    MessageLine,
+    Gutter,
+    Selection,
+    SearchMatch,
+    CursorLine,
+    Ruler,
+    MatchingBracket,
+    Syntax(TokenType),
+}
+
+pub struct Theme {
+    name: String,
+    styles: HashMap<ThemeElement, Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        builtin(DEFAULT_THEME).expect("the default theme is built in")
+    }
+}
+
+impl Theme {
+    /// Loads `name` from the user themes directory, falling back to the
+    /// built-in theme of that name. Names that would reach outside the
+    /// themes directory are rejected.
+    pub fn load(name: &str) -> error::Result<Self> {
+        if name.contains(['/', '\\']) || name.contains("..") {
+            return Err(Error::Config(format!("invalid theme name `{name}`")));
+        }
+        if let Some(path) = user_theme_path(name).filter(|path| path.is_file()) {
+            let origin = path.display().to_string();
+            let source = fs::read_to_string(&path).map_err(|err| Error::document(&origin, err))?;
+            return parse(name, &source, &origin);
+        }
+        builtin(name).ok_or_else(|| Error::Config(format!("unknown theme `{name}`")))
+    }
+
+    /// Lists the built-in and user theme names.
+    pub fn available() -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_THEMES
+            .iter()
+            .map(|(name, _)| String::from(*name))
+            .collect();
+        let user_themes = config::config_dir()
+            .and_then(|dir| fs::read_dir(dir.join(THEMES_DIR)).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != THEME_FILE_EXTENSION {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_owned())
+            });
+        names.extend(user_themes);
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the style of `element`. Colors it leaves unset are those of
+    /// the text.
+    pub fn style(&self, element: ThemeElement) -> Style {
+        let text = self
+            .styles
+            .get(&ThemeElement::Text)
+            .copied()
+            .unwrap_or_default();
+        self.style_over(element, text)
+    }
+
+    /// Returns the style of `element` drawn over a cell styled `base`.
+    pub fn style_over(&self, element: ThemeElement, base: Style) -> Style {
+        let style = self
+            .styles
+            .get(&element)
+            .map_or(base, |style| style.over(base));
+        Style {
+            highlight: style.highlight || element.is_highlight(),
+            ..style
+        }
+    }
+}
+
+impl ThemeElement {
+    /// Whether the element stands out from the text, which is what a
+    /// terminal without colors can still show.
+    fn is_highlight(self) -> bool {
+        matches!(
+            self,
+            ThemeElement::StatusBar
+                | ThemeElement::InactiveStatusBar
+                | ThemeElement::Selection
+                | ThemeElement::SearchMatch
+                | ThemeElement::MatchingBracket
+        )
+    }
+}
+
+fn user_theme_path(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{name}.{THEME_FILE_EXTENSION}");
+    Some(config::config_dir()?.join(THEMES_DIR).join(file_name))
+}
+
+fn builtin(name: &str) -> Option<Theme> {
+    let (name, source) = BUILTIN_THEMES
+        .iter()
+        .find(|(builtin_name, _)| *builtin_name == name)?;
+    Some(parse(name, source, "built-in theme").expect("built-in themes are valid"))
+}
+
+/// Parses a theme file. `origin` names the file in error messages.
+fn parse(name: &str, source: &str, origin: &str) -> error::Result<Theme> {
+    let mut styles = HashMap::new();
+    config::parse_entries(source, origin, |key, value| {
+        if key == BASE_KEY {
+            let base = builtin(value).ok_or_else(|| format!("unknown base theme `{value}`"))?;
+            for (element, style) in base.styles {
+                styles.entry(element).or_insert(style);
+            }
+            return Ok(());
+        }
+
+        let (_, element) = ELEMENT_NAMES
+            .iter()
+            .find(|(element_name, _)| *element_name == key)
+            .ok_or_else(|| format!("unknown element `{key}`"))?;
+        styles.insert(*element, parse_style(value)?);
+        Ok(())
+    })?;
+
+    Ok(Theme {
+        name: String::from(name),
+        styles,
+    })
+}
+
+fn parse_style(value: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    for word in value.split_whitespace() {
+        let attributes = &mut style.attributes;
+        match word {
+            "bold" => attributes.bold = true,
+            "italic" => attributes.italic = true,
+            "underline" => attributes.underline = true,
+            "reverse" => attributes.reverse = true,
+            _ => {
+                let invalid_color = || format!("invalid color `{word}`");
+                if let Some(color) = word.strip_prefix("fg:") {
+                    style.foreground = Some(parse_color(color).ok_or_else(invalid_color)?);
+                } else if let Some(color) = word.strip_prefix("bg:") {
+                    style.background = Some(parse_color(color).ok_or_else(invalid_color)?);
+                } else {
+                    return Err(format!("unknown style `{word}`"));
+                }
+            },
+        }
+    }
+    Ok(style)
+}
+
+/// Parses a `#rrggbb` color.
+fn parse_color(color: &str) -> Option<Rgb> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+    Some((channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::Attributes;
+
+    #[test]
+    fn builtin_themes_parse() {
+        for (name, _) in BUILTIN_THEMES {
+            assert_eq!(builtin(name).unwrap().name(), name);
+        }
+        assert!(builtin("missing").is_none());
+    }
+
+    #[test]
+    fn styles_inherit_text_colors_and_base_theme() {
+        let theme = parse(
+            "custom",
+            "# comment\ntext = bg:#101010\nbase = dark\nselection = fg:#FFFFFF bold underline\n",
+            "custom.theme",
+        )
+        .unwrap();
+
+        assert_eq!(
+            theme.style(ThemeElement::Selection),
+            Style {
+                background: Some((16, 16, 16)),
+                foreground: Some((255, 255, 255)),
+                attributes: Attributes {
+                    bold: true,
+                    underline: true,
+                    ..Attributes::default()
+                },
+                highlight: true,
+            }
+        );
+        assert_eq!(
+            theme.style(ThemeElement::StatusBar),
+            Theme::default().style(ThemeElement::StatusBar)
+        );
+        assert_eq!(
+            theme.style(ThemeElement::Gutter).background,
+            Some((16, 16, 16))
+        );
+        assert!(!theme.style(ThemeElement::Text).highlight);
+    }
+
+    #[test]
+    fn rejects_names_outside_themes_directory() {
+        for name in ["../dark", "themes/dark", "..\\dark", ".."] {
+            let expected = format!("invalid theme name `{name}`");
+            assert!(
+                matches!(Theme::load(name), Err(Error::Config(message)) if message == expected),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        let error = |source| match parse("bad", source, "bad.theme") {
+            Err(err) => err.to_string(),
+            Ok(_) => String::new(),
+        };
+        assert_eq!(
+            error("text = bold\nstatus = bold"),
+            "invalid configuration: bad.theme:2: unknown element `status`"
+        );
+        assert_eq!(
+            error("text = fg:#12345"),
+            "invalid configuration: bad.theme:1: invalid color `fg:#12345`"
+        );
+        assert_eq!(
+            error("text = blink"),
+            "invalid configuration: bad.theme:1: unknown style `blink`"
+        );
+        assert_eq!(
+            error("text"),
+            "invalid configuration: bad.theme:1: expected `key = value`"
+        );
+    }
+}